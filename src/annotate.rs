@@ -0,0 +1,321 @@
+use std::{
+    io::Write,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use august_build::{
+    diagnostics::{Diagnostic, Position, RuntimeErrorFormatter, Severity},
+    runtime::{Notifier, NotifierEvent},
+};
+
+use crate::cli::ErrorFormat;
+
+/// Writes errors, warnings and unit output sections in a form understood by CI services.
+///
+/// With [`ErrorFormat::Github`] errors become `::error` workflow commands
+/// and the commands of each unit are folded with `::group::`.
+/// With [`ErrorFormat::Gitlab`] errors are written as `file:line:col: error: message`
+/// and units are folded with collapsible sections.
+pub struct Annotator {
+    format: ErrorFormat,
+    file_name: String,
+    code: String,
+    out: Mutex<Box<dyn Write + Send>>,
+    /// Unit whose section is currently open
+    group: Mutex<Option<String>>,
+}
+
+impl Annotator {
+    pub fn new(
+        format: ErrorFormat,
+        file_name: impl Into<String>,
+        code: impl Into<String>,
+        out: impl Write + Send + 'static,
+    ) -> Self {
+        Self {
+            format,
+            file_name: file_name.into(),
+            code: code.into(),
+            out: Mutex::new(Box::new(out)),
+            group: Mutex::default(),
+        }
+    }
+
+    pub fn annotate(&self, diagnostics: impl IntoIterator<Item = Diagnostic>) {
+        let mut out = self.out.lock().unwrap();
+
+        for d in diagnostics {
+            let severity = match d.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            let Position {
+                line,
+                column: col,
+                end_line,
+                end_column: end_col,
+            } = Position::of(&d.span, &self.code);
+            match self.format {
+                ErrorFormat::Human => {}
+                ErrorFormat::Github => {
+                    let title = d.code.map(|c| format!(",title={c}")).unwrap_or_default();
+                    writeln!(
+                        out,
+                        "::{} file={},line={line},col={col},endLine={end_line},endColumn={end_col}{title}::{}",
+                        severity,
+                        escape_property(&self.file_name),
+                        escape_data(&d.message)
+                    )
+                    .ok();
+                }
+                ErrorFormat::Gitlab => {
+                    let code = d.code.map(|c| format!("[{c}] ")).unwrap_or_default();
+                    writeln!(
+                        out,
+                        "{}:{line}:{col}: {severity}: {code}{}",
+                        self.file_name, d.message
+                    )
+                    .ok();
+                }
+            }
+        }
+        out.flush().ok();
+    }
+
+    fn open_group(&self, unit: &str) {
+        let mut group = self.group.lock().unwrap();
+        if group.as_deref() == Some(unit) {
+            return;
+        }
+        if let Some(open) = group.take() {
+            self.write_group_end(&open);
+        }
+        self.write_group_start(unit);
+        *group = Some(unit.to_owned());
+    }
+
+    fn close_group(&self, unit: &str) {
+        let mut group = self.group.lock().unwrap();
+        if group.as_deref() == Some(unit) {
+            self.write_group_end(unit);
+            *group = None;
+        }
+    }
+
+    fn write_group_start(&self, unit: &str) {
+        let mut out = self.out.lock().unwrap();
+        match self.format {
+            ErrorFormat::Human => {}
+            ErrorFormat::Github => {
+                writeln!(out, "::group::{}", escape_data(unit)).ok();
+            }
+            ErrorFormat::Gitlab => {
+                writeln!(
+                    out,
+                    "\x1b[0Ksection_start:{}:{}\r\x1b[0K{unit}",
+                    unix_time(),
+                    section_name(unit)
+                )
+                .ok();
+            }
+        }
+        out.flush().ok();
+    }
+
+    fn write_group_end(&self, unit: &str) {
+        let mut out = self.out.lock().unwrap();
+        match self.format {
+            ErrorFormat::Human => {}
+            ErrorFormat::Github => {
+                writeln!(out, "::endgroup::").ok();
+            }
+            ErrorFormat::Gitlab => {
+                writeln!(
+                    out,
+                    "\x1b[0Ksection_end:{}:{}\r\x1b[0K",
+                    unix_time(),
+                    section_name(unit)
+                )
+                .ok();
+            }
+        }
+        out.flush().ok();
+    }
+}
+
+impl Notifier for Annotator {
+    fn on_event(&self, event: NotifierEvent<'_>) {
+        match event {
+            // Opened on the first command rather than on start,
+            // so the commands of dependencies aren't nested inside
+            NotifierEvent::Call { unit, .. } => self.open_group(unit),
+            NotifierEvent::Complete(name) | NotifierEvent::UnitFailed(name) => {
+                self.close_group(name);
+            }
+            NotifierEvent::Error { errors, .. } => self.annotate(
+                RuntimeErrorFormatter::new(errors, &self.file_name, &self.code).diagnostics(),
+            ),
+            NotifierEvent::CommandComplete { .. }
+            | NotifierEvent::Start(_)
+            | NotifierEvent::UnitSkipped(_)
+            | NotifierEvent::Dependency { .. }
+            | NotifierEvent::BlockOn { .. }
+            | NotifierEvent::Output { .. } => {}
+            // Events added to the library after this notifier
+            _ => {}
+        }
+    }
+}
+
+/// Escapes the message of a workflow command.
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes the value of a workflow command property.
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// GitLab section names may only contain letters, numbers, `_`, `.` and `-`.
+fn section_name(unit: &str) -> String {
+    unit.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use august_build::{
+        parse_str,
+        runtime::{Notifier, NotifierEvent, RuntimeError},
+        Command,
+    };
+
+    use super::Annotator;
+    use crate::cli::ErrorFormat;
+
+    const CODE: &str = "unit Build {\n    exec(false)\n}\n";
+
+    /// Output shared with the annotator, so it can be read after the events are sent.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    /// Runs the events of `Build` failing through an annotator.
+    fn annotate(format: ErrorFormat) -> String {
+        let module = parse_str("main.august", CODE).unwrap();
+        let cmd = &module.units()[0].commands()[0];
+        let Command::Exec(args) = cmd else {
+            panic!("expected exec, found {cmd}");
+        };
+        let errors = [RuntimeError::ExecutionFailure(
+            args.clone(),
+            io::Error::other("Process returned non-successfully with exit status: 1."),
+        )];
+
+        let out = Buffer::default();
+        let annotator = Annotator::new(format, "main.august", CODE, out.clone());
+        annotator.on_event(NotifierEvent::Start("Build"));
+        annotator.on_event(NotifierEvent::Call { unit: "Build", cmd });
+        annotator.on_event(NotifierEvent::UnitFailed("Build"));
+        annotator.on_event(NotifierEvent::Error {
+            unit: "Build",
+            errors: &errors,
+        });
+
+        out.text()
+    }
+
+    /// Replaces the timestamps of GitLab sections, which change between runs.
+    fn strip_times(text: &str) -> String {
+        text.split("section_")
+            .enumerate()
+            .map(|(i, part)| match part.split_once(':') {
+                Some((kind, rest)) if i > 0 => {
+                    let rest = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+                    format!("section_{kind}:<time>{rest}")
+                }
+                _ => part.to_owned(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn github() {
+        assert_eq!(
+            annotate(ErrorFormat::Github),
+            "::group::Build\n\
+             ::endgroup::\n\
+             ::error file=main.august,line=2,col=10,endLine=2,endColumn=15,title=A0203::\
+             Failed to execute false: Process returned non-successfully with exit status: 1.\n"
+        );
+    }
+
+    #[test]
+    fn gitlab() {
+        assert_eq!(
+            strip_times(&annotate(ErrorFormat::Gitlab)),
+            "\x1b[0Ksection_start:<time>:build\r\x1b[0KBuild\n\
+             \x1b[0Ksection_end:<time>:build\r\x1b[0K\n\
+             main.august:2:10: error: [A0203] \
+             Failed to execute false: Process returned non-successfully with exit status: 1.\n"
+        );
+    }
+
+    #[test]
+    fn github_escapes() {
+        let out = Buffer::default();
+        let annotator = Annotator::new(ErrorFormat::Github, "a,b:c.august", CODE, out.clone());
+        annotator.on_event(NotifierEvent::Call {
+            unit: "100%",
+            cmd: &Command::Do(Vec::new()),
+        });
+        annotator.annotate([august_build::diagnostics::Diagnostic::error(
+            0..4,
+            "first\nsecond",
+        )]);
+
+        assert_eq!(
+            out.text(),
+            "::group::100%25\n\
+             ::error file=a%2Cb%3Ac.august,line=1,col=1,endLine=1,endColumn=5::first%0Asecond\n"
+        );
+    }
+}
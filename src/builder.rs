@@ -0,0 +1,273 @@
+//! Building a [`Module`] from Rust instead of a build script.
+//!
+//! Builders produce the same [`AST`] as the parser,
+//! so a built module goes through exactly the same checks as [`Module::lower`].
+//! Names and arguments have no span, as they don't come from a build script.
+//!
+//! ```
+//! use august_build::{
+//!     builder::{ModuleBuilder, UnitBuilder},
+//!     Pragma,
+//! };
+//!
+//! let module = ModuleBuilder::new()
+//!     .expose(Pragma::Build, "Build")
+//!     .unit(
+//!         UnitBuilder::new("Build")
+//!             .meta("desc", "Builds the project")
+//!             .depends_on(["Test"])
+//!             .exec(["cargo", "build"]),
+//!     )
+//!     .unit(UnitBuilder::new("Test").exec(["cargo", "test"]))
+//!     .build()
+//!     .unwrap();
+//!
+//! assert_eq!(module.unit_by_pragma(Pragma::Build).as_deref(), Some("Build"));
+//! ```
+
+use crate::{
+    parser::{Spanned, AST},
+    registry::CommandRegistry,
+    Command, LowerError, Module, Pragma, Unit,
+};
+
+/// Collects exposes and units, checking them all at once in [`ModuleBuilder::build`].
+#[derive(Debug, Clone, Default)]
+pub struct ModuleBuilder {
+    ast: Vec<AST>,
+}
+
+impl ModuleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Same as `expose <unit> as <pragma>`.
+    #[inline]
+    pub fn expose(mut self, pragma: Pragma, unit: impl Into<String>) -> Self {
+        self.ast
+            .push(AST::Expose(pragma, Spanned::new(unit.into())));
+        self
+    }
+
+    #[inline]
+    pub fn unit(mut self, unit: UnitBuilder) -> Self {
+        self.ast
+            .push(AST::Unit(Spanned::new(unit.name), unit.commands));
+        self
+    }
+
+    /// Lowers the module, returning every error found,
+    /// such as duplicate units or dependencies on units that don't exist.
+    pub fn build(self) -> Result<Module, Vec<LowerError>> {
+        Module::lower(self.ast)
+    }
+
+    /// Same as [`ModuleBuilder::build`], allowing the custom commands in `registry`.
+    pub fn build_with(self, registry: &CommandRegistry) -> Result<Module, Vec<LowerError>> {
+        Module::lower_with(self.ast, registry)
+    }
+
+    /// The module as if it had been parsed from a build script.
+    pub fn into_ast(self) -> Vec<AST> {
+        self.ast
+    }
+}
+
+/// Commands of a unit, in the order they're added.
+#[derive(Debug, Clone)]
+pub struct UnitBuilder {
+    name: String,
+    commands: Vec<Command>,
+}
+
+impl UnitBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            commands: Vec::new(),
+        }
+    }
+
+    /// Same as `depends_on(...)`.
+    #[inline]
+    pub fn depends_on<S: Into<String>>(self, units: impl IntoIterator<Item = S>) -> Self {
+        self.command(Command::DependsOn(spanned(units)))
+    }
+
+    /// Same as `meta(@<name> "<value>")`.
+    #[inline]
+    pub fn meta(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.command(Command::Meta(vec![(
+            Spanned::new(name.into()),
+            value.into(),
+        )]))
+    }
+
+    /// Same as `do(...)`.
+    #[inline]
+    pub fn run<S: Into<String>>(self, units: impl IntoIterator<Item = S>) -> Self {
+        self.command(Command::Do(spanned(units)))
+    }
+
+    /// Same as `exec(...)`, the first argument is the program to run.
+    #[inline]
+    pub fn exec<S: Into<String>>(self, args: impl IntoIterator<Item = S>) -> Self {
+        self.command(Command::Exec(spanned(args)))
+    }
+
+    /// Same as `concurrent { ... }`.
+    #[inline]
+    pub fn concurrent(self, cmds: impl IntoIterator<Item = Command>) -> Self {
+        self.command(Command::Concurrent(
+            cmds.into_iter().map(Box::new).collect(),
+        ))
+    }
+
+    /// Same as `<module>::<name>("arg", ...)`, calling a command from a [`CommandRegistry`].
+    #[inline]
+    pub fn custom<S: Into<String>>(
+        self,
+        module: impl Into<String>,
+        name: impl Into<String>,
+        args: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.command(Command::Custom(
+            Spanned::new(module.into()),
+            Spanned::new(name.into()),
+            spanned(args),
+        ))
+    }
+
+    /// Adds any other command, such as those from the `fs`, `io` and `env` modules.
+    #[inline]
+    pub fn command(mut self, cmd: Command) -> Self {
+        self.commands.push(cmd);
+        self
+    }
+
+    /// Lowers the unit by itself, without checking that the units it refers to exist.
+    pub fn build(&self) -> Result<Unit, Vec<LowerError>> {
+        Unit::lower(&self.commands)
+    }
+}
+
+fn spanned<S: Into<String>>(items: impl IntoIterator<Item = S>) -> Vec<Spanned<String>> {
+    items.into_iter().map(|s| Spanned::new(s.into())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ModuleBuilder, UnitBuilder};
+    use crate::{parser::Spanned, registry::CommandRegistry, LowerError, Pragma};
+
+    /// Registry with a `docker::build` command that does nothing.
+    fn registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::new();
+        registry
+            .module("docker")
+            .unwrap()
+            .command("build", |_| async { Ok(()) });
+        registry
+    }
+
+    #[test]
+    fn rejects_duplicate_units() {
+        let errors = ModuleBuilder::new()
+            .unit(UnitBuilder::new("Build").exec(["cargo", "build"]))
+            .unit(UnitBuilder::new("Build").exec(["make"]))
+            .build()
+            .unwrap_err();
+
+        assert!(
+            matches!(&errors[..], [LowerError::DuplicateUnit(a, b)] if a.inner() == "Build" && b.inner() == "Build"),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_exposes() {
+        let errors = ModuleBuilder::new()
+            .expose(Pragma::Build, "Build")
+            .expose(Pragma::Build, "Test")
+            .unit(UnitBuilder::new("Build").exec(["cargo", "build"]))
+            .unit(UnitBuilder::new("Test").exec(["cargo", "test"]))
+            .build()
+            .unwrap_err();
+
+        assert!(
+            matches!(&errors[..], [LowerError::DuplicateExpose(Pragma::Build, _)]),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_dependencies() {
+        let errors = ModuleBuilder::new()
+            .unit(
+                UnitBuilder::new("Build")
+                    .depends_on(["Tset"])
+                    .run(["Missing"]),
+            )
+            .unit(UnitBuilder::new("Test").exec(["cargo", "test"]))
+            .build()
+            .unwrap_err();
+
+        assert_eq!(
+            errors,
+            [
+                LowerError::NameError(Spanned::new("Tset".to_owned()), Some("Test".to_owned())),
+                LowerError::NameError(Spanned::new("Missing".to_owned()), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_custom_commands() {
+        let module = || {
+            ModuleBuilder::new()
+                .unit(UnitBuilder::new("Image").custom("docker", "build", ["app"]))
+                .unit(UnitBuilder::new("Push").custom("docker", "pusj", ["app"]))
+        };
+
+        let errors = module().build().unwrap_err();
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(errors
+            .iter()
+            .all(|e| matches!(e, LowerError::UnknownCommand(..))));
+
+        let errors = module().build_with(&registry()).unwrap_err();
+        assert!(
+            matches!(&errors[..], [LowerError::UnknownCommand(m, c, _)] if m.inner() == "docker" && c.inner() == "pusj"),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn allows_registered_custom_commands() {
+        let module = ModuleBuilder::new()
+            .expose(Pragma::Build, "Image")
+            .unit(UnitBuilder::new("Image").custom("docker", "build", ["app"]))
+            .build_with(&registry())
+            .unwrap();
+
+        assert_eq!(
+            module.unit_by_pragma(Pragma::Build).as_deref(),
+            Some("Image")
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_dependencies_in_a_unit() {
+        let errors = UnitBuilder::new("Build")
+            .depends_on(["Test"])
+            .depends_on(["Test"])
+            .build()
+            .unwrap_err();
+
+        assert!(
+            matches!(&errors[..], [LowerError::DuplicateDependency(_, d)] if d.inner() == "Test"),
+            "{errors:?}"
+        );
+    }
+}
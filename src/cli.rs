@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 
+use august_build::{lint::Lint, runtime::OutputMode};
 use clap::{Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
 
@@ -18,6 +19,27 @@ pub struct Cli {
 
     #[arg(global(true), long, value_enum, default_value_t, alias("color"))]
     pub colour: ColourSupport,
+    /// Format of the logging output produced during unit execution
+    #[arg(global(true), long, value_enum, default_value_t)]
+    pub log_format: LogFormat,
+    /// Format of the errors produced when parsing the build script
+    #[arg(global(true), long, value_enum, default_value_t)]
+    pub message_format: MessageFormat,
+    /// Additionally writes errors to stdout in a form understood by a CI service
+    #[arg(global(true), long, value_enum, default_value_t)]
+    pub error_format: ErrorFormat,
+    /// How output from processes spawned by `exec` is written
+    #[arg(global(true), long, value_enum, default_value_t)]
+    pub output: Output,
+    /// Shows the output of `exec` only when the process fails, same as `--output quiet-success`
+    #[arg(global(true), long)]
+    pub quiet_success: bool,
+    /// Writes the output and events of each unit to log files in a new directory for each run
+    #[arg(global(true), long)]
+    pub log_dir: Option<PathBuf>,
+    /// Writes a Chrome Trace Event Format file of the run to this path
+    #[arg(global(true), long)]
+    pub trace: Option<PathBuf>,
 
     #[command(subcommand)]
     pub subcommand: CLICommand,
@@ -28,15 +50,46 @@ pub enum CLICommand {
     /// Provides information about the CLI
     Info,
     /// Parses the build script and displays related information
-    Inspect,
-    /// Parses the build script to check for errors
-    Check,
+    Inspect {
+        /// Prints the whole lowered module as JSON, including spans
+        #[arg(long)]
+        json: bool,
+    },
+    /// Parses the build script to check for errors and warnings
+    Check {
+        /// Fails if there are any warnings
+        #[arg(long)]
+        deny_warnings: bool,
+        /// Lints to not report
+        #[arg(short = 'A', long, value_name = "LINT")]
+        allow: Vec<Lint>,
+        /// Lints to report as warnings
+        #[arg(short = 'W', long, value_name = "LINT")]
+        warn: Vec<Lint>,
+        /// Lints to report as errors
+        #[arg(short = 'D', long, value_name = "LINT")]
+        deny: Vec<Lint>,
+    },
+    /// Rewrites the build script in the canonical style
+    Fmt {
+        /// Fails if the build script isn't already formatted, instead of rewriting it
+        #[arg(long)]
+        check: bool,
+    },
+    /// Starts a language server for editors, communicating over stdin and stdout
+    Lsp,
+    /// Explains an error code, like A0106, with examples
+    Explain { code: String },
     /// Writes command line shell completions to stdout
     Completions { shell: Shell },
     /// Runs the unit exposed to `build`
     Build,
     /// Runs the unit exposed to `test`
-    Test,
+    Test {
+        /// Writes a JUnit XML report with a test case for each unit that ran to this path
+        #[arg(long)]
+        junit: Option<PathBuf>,
+    },
     /// Runs the unit provided as an argument
     Run { unit: String },
 }
@@ -48,3 +101,58 @@ pub enum ColourSupport {
     Auto,
     Never,
 }
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Coloured log lines for reading in a terminal
+    #[default]
+    Human,
+    /// One JSON object per line for each runtime event
+    Json,
+    /// Live view of running units, falling back to `human` when stderr isn't a terminal
+    Progress,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum MessageFormat {
+    /// Error reports pointing to the build script, written to stderr
+    #[default]
+    Human,
+    /// A JSON object for each diagnostic, written to stdout
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat {
+    /// Only the usual error reports
+    #[default]
+    Human,
+    /// GitHub Actions workflow commands, with a folded group for each unit
+    Github,
+    /// `file:line:col` error lines, with a collapsible section for each unit
+    Gitlab,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum Output {
+    /// Processes write directly to the terminal
+    #[default]
+    Inherit,
+    /// Each line is tagged with the name of the unit that wrote it
+    Prefixed,
+    /// Output is buffered and written all at once when the process exits
+    Grouped,
+    /// Output is captured and only shown in the error report if the process fails
+    QuietSuccess,
+}
+
+impl From<Output> for OutputMode {
+    fn from(value: Output) -> Self {
+        match value {
+            Output::Inherit => Self::Inherit,
+            Output::Prefixed => Self::Prefixed,
+            Output::Grouped => Self::Grouped,
+            Output::QuietSuccess => Self::QuietSuccess,
+        }
+    }
+}
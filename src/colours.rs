@@ -1,3 +1,7 @@
+//! Extensions of [`owo_colors::OwoColorize`] that only colour text when the stream supports it.
+//!
+//! Requires the `diagnostics` feature.
+
 macro_rules! owo_colors_ext {
     ($trait_name:ident $stream_type:ident
 ---
@@ -100,3 +104,11 @@ gen_ext! {
     OwoColorizeStdoutSupported Stdout
     OwoColorizeStderrSupported Stderr
 }
+
+/// Whether the traits above colour text written to `stream`,
+/// for choosing whether to colour output that doesn't go through them.
+pub fn supports_colour(stream: owo_colors::Stream) -> bool {
+    use owo_colors::OwoColorize;
+
+    ' '.if_supports_color(stream, OwoColorize::red).to_string() != " "
+}
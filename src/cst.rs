@@ -0,0 +1,335 @@
+//! Lossless concrete syntax tree of a build script.
+//!
+//! Unlike the [`AST`], it keeps the whitespace between tokens,
+//! so the build script can be written back exactly as it was read.
+//! Build scripts can't contain comments, so whitespace is the only trivia.
+//! Text the lexer couldn't read is kept too, so even invalid build scripts round-trip.
+
+use std::{fmt::Display, mem::take, ops::Range};
+
+use chumsky::{Parser, Stream};
+
+use crate::{
+    lexer::{lexer, Delim, Token},
+    parser::{parser, AST},
+    SyntaxError,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Kind {
+    Token(Token),
+    /// Spaces, tabs and newlines between tokens
+    Whitespace,
+    /// Text the lexer couldn't read
+    Error,
+}
+
+/// A token or the whitespace between two tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leaf {
+    pub kind: Kind,
+    /// Exactly as written in the build script
+    pub text: String,
+    /// Span in chars, without the whitespace the lexer includes around tokens
+    pub span: Range<usize>,
+}
+
+impl Leaf {
+    /// Whether the leaf is whitespace, which the parser ignores.
+    pub fn is_trivia(&self) -> bool {
+        self.kind == Kind::Whitespace
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Leaf(Leaf),
+    /// Everything between a pair of brackets or braces
+    Group {
+        delim: Delim,
+        open: Leaf,
+        children: Vec<Node>,
+        /// Missing if the group is never closed
+        close: Option<Leaf>,
+    },
+}
+
+impl Node {
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Self::Leaf(leaf) => leaf.span.clone(),
+            Self::Group {
+                open,
+                children,
+                close,
+                ..
+            } => {
+                let end = close
+                    .as_ref()
+                    .map(|c| c.span.end)
+                    .or_else(|| children.last().map(|c| c.span().end))
+                    .unwrap_or(open.span.end);
+                open.span.start..end
+            }
+        }
+    }
+
+    fn leaves<'a>(&'a self, out: &mut Vec<&'a Leaf>) {
+        match self {
+            Self::Leaf(leaf) => out.push(leaf),
+            Self::Group {
+                open,
+                children,
+                close,
+                ..
+            } => {
+                out.push(open);
+                children.iter().for_each(|c| c.leaves(out));
+                out.extend(close);
+            }
+        }
+    }
+}
+
+/// Every token and piece of whitespace in a build script,
+/// with tokens between brackets grouped together.
+///
+/// Displaying it gives back the build script it was parsed from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cst {
+    nodes: Vec<Node>,
+    /// Length of the build script in chars
+    len: usize,
+    /// Errors from the lexer
+    errors: Vec<SyntaxError<char>>,
+}
+
+impl Cst {
+    /// Splits a build script into leaves, keeping any errors from the lexer alongside them.
+    pub fn parse(code: &str) -> Self {
+        let chars = code.chars().collect::<Vec<_>>();
+        let len = chars.len();
+
+        let (tokens, errors) = lexer().parse_recovery(Stream::from_iter(
+            len..len + 1,
+            chars.iter().enumerate().map(|(i, c)| (*c, i..i + 1)),
+        ));
+
+        let leaf = |kind, span: Range<usize>| Leaf {
+            kind,
+            text: chars[span.clone()].iter().collect(),
+            span,
+        };
+        // Splits text between tokens into whitespace and anything the lexer skipped
+        let gap = |leaves: &mut Vec<Leaf>, span: Range<usize>| {
+            let mut start = span.start;
+            for i in span.clone() {
+                if chars[i].is_whitespace() != chars[start].is_whitespace() {
+                    leaves.push(leaf(kind_of(chars[start]), start..i));
+                    start = i;
+                }
+            }
+            if start < span.end {
+                leaves.push(leaf(kind_of(chars[start]), start..span.end));
+            }
+        };
+
+        let mut leaves = Vec::new();
+        let mut end = 0;
+        for (token, span) in tokens.unwrap_or_default() {
+            // The lexer's spans include the whitespace around each token
+            let mut start = span.start.max(end);
+            let mut stop = span.end.min(len);
+            while start < stop && chars[start].is_whitespace() {
+                start += 1;
+            }
+            while stop > start && chars[stop - 1].is_whitespace() {
+                stop -= 1;
+            }
+            if start == stop {
+                continue;
+            }
+
+            gap(&mut leaves, end..start);
+            let kind = match token {
+                // Text the lexer skipped to recover from an error
+                Token::Err(_) => Kind::Error,
+                token => Kind::Token(token),
+            };
+            leaves.push(leaf(kind, start..stop));
+            end = stop;
+        }
+        gap(&mut leaves, end..len);
+
+        Self {
+            nodes: tree(leaves),
+            len,
+            errors: errors.into_iter().map(SyntaxError::new).collect(),
+        }
+    }
+
+    /// Errors from the lexer, the text it couldn't read is kept as [`Kind::Error`] leaves.
+    pub fn errors(&self) -> &[SyntaxError<char>] {
+        &self.errors
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Every token and piece of whitespace in the order they're written.
+    pub fn leaves(&self) -> Vec<&Leaf> {
+        let mut out = Vec::new();
+        self.nodes.iter().for_each(|n| n.leaves(&mut out));
+        out
+    }
+
+    /// Tokens without whitespace, the same as the output of the lexer apart from the spans,
+    /// which don't include the whitespace around each token.
+    pub fn tokens(&self) -> Vec<(Token, Range<usize>)> {
+        self.leaves()
+            .into_iter()
+            .filter_map(|leaf| match &leaf.kind {
+                Kind::Token(t) => Some((t.clone(), leaf.span.clone())),
+                Kind::Whitespace | Kind::Error => None,
+            })
+            .collect()
+    }
+
+    /// Parses the tokens into the [`AST`].
+    pub fn ast(&self) -> Result<Vec<AST>, Vec<SyntaxError<Token>>> {
+        parser()
+            .parse(Stream::from_iter(
+                self.len..self.len + 1,
+                self.tokens().into_iter(),
+            ))
+            .map_err(|errors| errors.into_iter().map(SyntaxError::new).collect())
+    }
+}
+
+impl Display for Cst {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.leaves()
+            .into_iter()
+            .try_for_each(|leaf| f.write_str(&leaf.text))
+    }
+}
+
+fn kind_of(c: char) -> Kind {
+    if c.is_whitespace() {
+        Kind::Whitespace
+    } else {
+        Kind::Error
+    }
+}
+
+/// Groups leaves between matching brackets.
+///
+/// Closing brackets that don't match the innermost open group are left as leaves.
+fn tree(leaves: Vec<Leaf>) -> Vec<Node> {
+    // Groups that are still open, with the nodes that came before them
+    let mut stack: Vec<(Delim, Leaf, Vec<Node>)> = Vec::new();
+    let mut nodes = Vec::new();
+
+    for leaf in leaves {
+        match leaf.kind {
+            Kind::Token(Token::OpenDelim(delim)) => stack.push((delim, leaf, take(&mut nodes))),
+            Kind::Token(Token::CloseDelim(delim))
+                if stack.last().is_some_and(|(open, ..)| *open == delim) =>
+            {
+                let (delim, open, parent) = stack.pop().unwrap();
+                let children = std::mem::replace(&mut nodes, parent);
+                nodes.push(Node::Group {
+                    delim,
+                    open,
+                    children,
+                    close: Some(leaf),
+                });
+            }
+            _ => nodes.push(Node::Leaf(leaf)),
+        }
+    }
+
+    while let Some((delim, open, parent)) = stack.pop() {
+        let children = std::mem::replace(&mut nodes, parent);
+        nodes.push(Node::Group {
+            delim,
+            open,
+            children,
+            close: None,
+        });
+    }
+
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use chumsky::{prelude::*, Stream};
+
+    use super::{Cst, Kind};
+    use crate::{lexer::lexer, parser::parser};
+
+    const VALID: &[&str] = &[
+        include_str!("../syntax-examples/main.august"),
+        include_str!("../syntax-examples/new_syntax.august"),
+        "",
+        " \n\t\n",
+        "expose Build as build\r\n\r\nunit Build{~(cargo build)   }",
+        "unit Build {\n  meta(@desc \"Zeichen: äöü → \\\"ok\\\"\")\n}\n",
+    ];
+
+    const INVALID: &[&str] = &[
+        // Fails to lex
+        "unit Build {\n    exec(a \\ b)\n}\n",
+        "unit Build {\n    exec(\"bad \\q escape\")\n}\n",
+        // Lexes but fails to parse
+        "unit Build {\n    exec(cargo build\n",
+        "unit Build ) {\n    ]exec(cargo build)}\n}}",
+        "expose as\n\n",
+    ];
+
+    #[test]
+    fn round_trips_valid_scripts() {
+        for code in VALID {
+            let cst = Cst::parse(code);
+            assert!(cst.errors().is_empty(), "{code:?}");
+            assert_eq!(cst.to_string(), *code);
+        }
+    }
+
+    #[test]
+    fn round_trips_invalid_scripts() {
+        for code in INVALID {
+            assert_eq!(Cst::parse(code).to_string(), *code);
+        }
+    }
+
+    #[test]
+    fn keeps_text_that_fails_to_lex() {
+        for code in &INVALID[..2] {
+            let cst = Cst::parse(code);
+            assert!(!cst.errors().is_empty(), "{code:?}");
+            assert!(cst.leaves().iter().any(|leaf| leaf.kind == Kind::Error));
+        }
+    }
+
+    #[test]
+    fn matches_the_lexer_and_parser() {
+        for code in VALID {
+            let cst = Cst::parse(code);
+            let len = code.chars().count();
+            let tokens = lexer().parse(*code).unwrap();
+            assert!(cst
+                .tokens()
+                .iter()
+                .map(|(t, _)| t)
+                .eq(tokens.iter().map(|(t, _)| t)));
+
+            let ast = parser()
+                .parse(Stream::from_iter(len..len + 1, tokens.into_iter()))
+                .unwrap();
+            assert_eq!(cst.ast().unwrap(), ast);
+        }
+    }
+}
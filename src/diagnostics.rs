@@ -0,0 +1,755 @@
+//! Error reports for build scripts, the same as those shown by the `august` CLI.
+//!
+//! Requires the `diagnostics` feature.
+
+use ariadne::{Color, Config, Report, ReportKind, Source};
+use owo_colors::{Style, Styled};
+use std::{
+    fmt::Display,
+    hash::Hash,
+    io::{self, Write},
+};
+
+use crate::{
+    lint::{Level, Warning},
+    parser::{Spanned, CORE_COMMANDS, KEYWORDS, MODULES},
+    runtime::RuntimeError,
+    suggest::closest,
+    Diagnostics, LowerError, ParseErrors, SyntaxError, SyntaxErrorKind,
+};
+
+pub use diagnostic::{Diagnostic, Label, Position, Severity};
+
+mod diagnostic;
+
+/// Formatting construct for [`SyntaxError`]s.
+/// Implemented for all [`Display`] to support [`char`] and
+/// [`Token`](crate::lexer::Token) errors.
+pub struct ParserErrorFormatter<D>
+where
+    D: Display + Eq + Hash,
+{
+    errors: Vec<SyntaxError<D>>,
+    file_name: String,
+    code: String,
+    colour: bool,
+}
+
+impl<D> ParserErrorFormatter<D>
+where
+    D: Display + Eq + Hash,
+{
+    /// Creates a new [`ParserErrorFormatter`] from a [`Vec`] of [`SyntaxError`]s,
+    /// the name of the source file and it's contents.
+    pub fn new(
+        errors: Vec<SyntaxError<D>>,
+        file_name: impl Into<String>,
+        code: impl Into<String>,
+    ) -> Self {
+        Self {
+            errors,
+            file_name: file_name.into(),
+            code: code.into(),
+            colour: true,
+        }
+    }
+
+    /// Whether the reports are coloured, which they are by default.
+    #[inline]
+    pub fn with_colour(mut self, colour: bool) -> Self {
+        self.colour = colour;
+        self
+    }
+
+    /// Generates Ariadne [`Report`]'s for each error
+    /// and writes them to a [`Write`] implementor.
+    ///
+    /// This process should only produce valid UTF-8.
+    pub fn write_reports(&self, w: &mut dyn Write) -> io::Result<()> {
+        write_diagnostics(
+            &self.diagnostics(),
+            &self.file_name,
+            &self.code,
+            self.colour,
+            w,
+        )
+    }
+}
+
+impl<D> ParserErrorFormatter<D>
+where
+    D: Display + Eq + Hash,
+{
+    /// Suggests a command, module or keyword if an unexpected identifier looks like a typo of one.
+    ///
+    /// If the identifier follows a module prefix, only commands in that module are suggested.
+    /// An unexpected `(` after an identifier, such as in `dependson(Test)`,
+    /// is suggested for the identifier instead.
+    fn suggestion(&self, err: &SyntaxError<D>) -> Option<String> {
+        let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+        let chars = self.code.chars().collect::<Vec<_>>();
+        let found = err.found()?.to_string();
+
+        let (found, span) = if found.chars().all(is_ident) {
+            (found, err.span())
+        } else if found == "(" {
+            let mut end = err.span().start.min(chars.len());
+            while end > 0 && chars[end - 1].is_whitespace() {
+                end -= 1;
+            }
+            let start = chars[..end]
+                .iter()
+                .rposition(|c| !is_ident(*c))
+                .map_or(0, |i| i + 1);
+            if start == end {
+                return None;
+            }
+            (chars[start..end].iter().collect(), start..end)
+        } else {
+            return None;
+        };
+
+        let before = chars.iter().take(span.start).collect::<String>();
+        let after = chars.iter().skip(span.end).collect::<String>();
+
+        // Typo of the module in `fs::copy`
+        if after.trim_start().starts_with("::") {
+            return closest(&found, MODULES.iter().map(|(m, _)| *m)).map(ToOwned::to_owned);
+        }
+
+        // Typo of the command in `fs::copy`
+        if let Some(prefix) = before.trim_end().strip_suffix("::") {
+            let module = prefix
+                .rsplit(|c: char| !is_ident(c))
+                .next()
+                .unwrap_or_default();
+            let (_, commands) = MODULES
+                .iter()
+                .find(|(m, _)| m.eq_ignore_ascii_case(module))?;
+            return closest(&found, commands.iter().copied()).map(|c| format!("{module}::{c}"));
+        }
+
+        let commands = MODULES
+            .iter()
+            .flat_map(|(m, cmds)| cmds.iter().map(move |c| (format!("{m}::{c}"), *c)));
+        let names = CORE_COMMANDS
+            .iter()
+            .chain(KEYWORDS)
+            .map(|c| (c.to_string(), *c))
+            .chain(commands)
+            .collect::<Vec<_>>();
+        let best = closest(&found, names.iter().map(|(_, c)| *c))?;
+        names
+            .into_iter()
+            .find(|(_, c)| *c == best)
+            .map(|(name, _)| name)
+    }
+
+    /// Plain text versions of each error's report.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.errors
+            .iter()
+            .map(|err| {
+                let found = match err.found() {
+                    Some(f) => f.to_string(),
+                    None => "EOF".to_string(),
+                };
+                let label = err.label().unwrap_or("<unknown_pattern>");
+                let expected = err
+                    .expected()
+                    .map(|ex| match ex {
+                        Some(x) => x.to_string(),
+                        None => "<invalid_char>".to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                let expected = if expected.is_empty() {
+                    String::new()
+                } else {
+                    format!(", expected one of {}", expected.join(", "))
+                };
+
+                let diagnostic = match err.kind() {
+                    SyntaxErrorKind::Unexpected => Diagnostic::error(
+                        err.span(),
+                        format!("Unexpected {found} found when parsing {label}{expected}"),
+                    )
+                    .with_primary_label(format!("Unexpected {found}")),
+                    SyntaxErrorKind::Unclosed { span, delimiter } => Diagnostic::error(
+                        err.span(),
+                        format!(
+                            "Unclosed delimiter {delimiter} found when parsing {label}{expected}"
+                        ),
+                    )
+                    .with_primary_label(format!("Must be closed before {found}"))
+                    .with_label(span, format!("Delimiter {delimiter} is never closed")),
+                    SyntaxErrorKind::Custom(msg) => Diagnostic::error(err.span(), msg),
+                };
+                let diagnostic = diagnostic.with_code(err.code());
+                match self.suggestion(err) {
+                    Some(s) => diagnostic.with_help(format!("Did you mean {s}?")),
+                    None => diagnostic,
+                }
+            })
+            .collect()
+    }
+}
+
+impl<D> Display for ParserErrorFormatter<D>
+where
+    D: Display + Eq + Hash,
+{
+    /// ## Note
+    /// This method has two instances of error ignorance.
+    ///
+    /// IO errors from `write_reports` are ignored with `ok()` as it is writing to a local `Vec<u8>` buffer.
+    /// UTF-8 conversion is lossy, though `write_reports` should only produce valid UTF-8.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut buf = Vec::new();
+        self.write_reports(&mut buf).ok();
+        String::from_utf8_lossy(&buf).trim_end().fmt(f)
+    }
+}
+
+/// Formatting construct for [`LowerError`]
+pub struct LowerErrorFormatter {
+    errors: Vec<LowerError>,
+    file_name: String,
+    code: String,
+    colour: bool,
+}
+
+impl LowerErrorFormatter {
+    /// Creates a new [`LowerErrorFormatter`] from a [`Vec`] of [`LowerError`]s,
+    /// the name of the source file and it's contents.
+    pub fn new(
+        errors: Vec<LowerError>,
+        file_name: impl Into<String>,
+        code: impl Into<String>,
+    ) -> Self {
+        Self {
+            errors,
+            file_name: file_name.into(),
+            code: code.into(),
+            colour: true,
+        }
+    }
+
+    /// Whether the reports are coloured, which they are by default.
+    #[inline]
+    pub fn with_colour(mut self, colour: bool) -> Self {
+        self.colour = colour;
+        self
+    }
+
+    /// Generates Ariadne [`Report`]'s for each error
+    /// and writes them to a [`Write`] implementor.
+    ///
+    /// This process should only produce valid UTF-8.
+    pub fn write_reports(&self, w: &mut dyn Write) -> io::Result<()> {
+        write_diagnostics(
+            &self.diagnostics(),
+            &self.file_name,
+            &self.code,
+            self.colour,
+            w,
+        )
+    }
+}
+
+impl LowerErrorFormatter {
+    /// Plain text versions of each error's report.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        use LowerError::{
+            DuplicateDependency, DuplicateExpose, DuplicateMetaItem, DuplicateUnit,
+            InvalidMetaValue, LockDeadlock, LockOrder, NameError, UnknownCommand,
+        };
+
+        self.errors
+            .iter()
+            .map(|err| {
+                match err {
+                DuplicateExpose(pragma, unit) => Diagnostic::error(
+                    unit.span(),
+                    format!("Attempted to define another binding for pragma {pragma:?}"),
+                )
+                .with_help("Consider assigning this to a different pragma or removing this expose statement."),
+                DuplicateUnit(fst, snd) => Diagnostic::error(
+                    snd.span(),
+                    format!("Attempted to define multiple units with the name {snd}"),
+                )
+                .with_primary_label("Unit defined again here")
+                .with_label(fst.span(), "Unit first defined here")
+                .with_help("Remove or change the name of one of the unit definitions."),
+                DuplicateDependency(fst, snd) => Diagnostic::error(
+                    snd.span(),
+                    format!("Dependency {snd} defined multiple times in the same unit"),
+                )
+                .with_primary_label("Defined again here")
+                .with_label(fst.span(), "First defined here")
+                .with_help("Remove the duplicate dependency."),
+                DuplicateMetaItem(fst, snd) => Diagnostic::error(
+                    snd.span(),
+                    format!("Meta item {snd} defined multiple times in the same unit"),
+                )
+                .with_primary_label("Defined again here")
+                .with_label(fst.span(), "First defined here")
+                .with_help("Remove the duplicate meta item."),
+                InvalidMetaValue(var, val) => Diagnostic::error(
+                    var.span(),
+                    format!("Meta item {var} has an invalid value {val}"),
+                )
+                .with_primary_label("Invalid value given here")
+                .with_help("Check the documentation for the values this meta item accepts."),
+                NameError(unit, suggestion) => Diagnostic::error(
+                    unit.span(),
+                    format!("Identifier refers to a unit {unit} that doesn't exist"),
+                )
+                .with_primary_label("Undefined unit")
+                .with_help(match suggestion {
+                    Some(s) => format!("Did you mean {s}?"),
+                    None => format!(
+                        "Define a unit with the name {unit} or change the unit being referred to."
+                    ),
+                }),
+                UnknownCommand(module, name, suggestion) => Diagnostic::error(
+                    module.span().start..name.span().end,
+                    format!("Refers to a command {module}::{name} that doesn't exist"),
+                )
+                .with_primary_label("Undefined command")
+                .with_help(match suggestion {
+                    Some(s) => format!("Did you mean {s}?"),
+                    None => "Custom commands have to be registered by the program running the build script."
+                        .to_owned(),
+                }),
+                LockDeadlock(call, held, lock) => Diagnostic::error(
+                    call.span(),
+                    format!("Calls {call} while holding lock {lock}, which {held} also needs"),
+                )
+                .with_primary_label("Called while holding the lock")
+                .with_label(held.span(), "Needs the same lock")
+                .with_help("Locks can't be taken twice, so this would never finish. Use a dependency instead of do, or give the units different locks."),
+                LockOrder(call, taker, held, taken) => Diagnostic::error(
+                    call.span(),
+                    format!("Calls {call} while holding lock {held}, so {taker} takes lock {taken}, which other units hold while taking lock {held}"),
+                )
+                .with_primary_label("Called while holding the lock")
+                .with_label(taker.span(), format!("Takes lock {taken}"))
+                .with_help("Units taking the same locks in a different order can wait on each other forever. Take the locks in the same order everywhere, or use a dependency instead of do."),
+                }
+                .with_code(err.code())
+            })
+            .collect()
+    }
+}
+
+impl Display for LowerErrorFormatter {
+    /// ## Note
+    /// This method has two instances of error ignorance.
+    ///
+    /// IO errors from `write_reports` are ignored with `ok()` as it is writing to a local `Vec<u8>` buffer.
+    /// UTF-8 conversion is lossy, though `write_reports` should only produce valid UTF-8.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut buf = Vec::new();
+        self.write_reports(&mut buf).ok();
+        String::from_utf8_lossy(&buf).trim_end().fmt(f)
+    }
+}
+
+/// Formatting construct for lint [`Warning`]s
+pub struct LintFormatter {
+    warnings: Vec<Warning>,
+    file_name: String,
+    code: String,
+    colour: bool,
+}
+
+impl LintFormatter {
+    /// Creates a new [`LintFormatter`] from a [`Vec`] of [`Warning`]s,
+    /// the name of the source file and it's contents.
+    pub fn new(
+        warnings: Vec<Warning>,
+        file_name: impl Into<String>,
+        code: impl Into<String>,
+    ) -> Self {
+        Self {
+            warnings,
+            file_name: file_name.into(),
+            code: code.into(),
+            colour: true,
+        }
+    }
+
+    /// Whether the reports are coloured, which they are by default.
+    #[inline]
+    pub fn with_colour(mut self, colour: bool) -> Self {
+        self.colour = colour;
+        self
+    }
+
+    /// Whether any of the warnings are denied.
+    pub fn has_errors(&self) -> bool {
+        self.warnings.iter().any(|w| w.level == Level::Deny)
+    }
+
+    /// Generates Ariadne [`Report`]'s for each warning
+    /// and writes them to a [`Write`] implementor.
+    ///
+    /// This process should only produce valid UTF-8.
+    pub fn write_reports(&self, w: &mut dyn Write) -> io::Result<()> {
+        write_diagnostics(
+            &self.diagnostics(),
+            &self.file_name,
+            &self.code,
+            self.colour,
+            w,
+        )
+    }
+
+    /// Plain text versions of each warning's report.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.warnings
+            .iter()
+            .map(|w| {
+                let mut d = Diagnostic::error(w.span.clone(), &w.message).with_code(w.lint.code());
+                if w.level != Level::Deny {
+                    d.severity = Severity::Warning;
+                }
+                for (span, message) in &w.labels {
+                    d = d.with_label(span.clone(), message);
+                }
+                d = d.with_note(format!(
+                    "The {} lint is set to {}",
+                    w.lint.name(),
+                    match w.level {
+                        Level::Deny => "deny",
+                        _ => "warn",
+                    }
+                ));
+                match &w.help {
+                    Some(help) => d.with_help(help),
+                    None => d,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Formatting construct for [`RuntimeError`]s
+pub struct RuntimeErrorFormatter<'a> {
+    errors: &'a [RuntimeError],
+    file_name: String,
+    code: String,
+    colour: bool,
+}
+
+impl<'a> RuntimeErrorFormatter<'a> {
+    /// Creates a new [`RuntimeErrorFormatter`] from a slice of [`RuntimeError`]s,
+    /// the name of the source file and it's contents.
+    pub fn new(
+        errors: &'a [RuntimeError],
+        file_name: impl Into<String>,
+        code: impl Into<String>,
+    ) -> Self {
+        Self {
+            errors,
+            file_name: file_name.into(),
+            code: code.into(),
+            colour: true,
+        }
+    }
+
+    /// Whether the reports are coloured, which they are by default.
+    #[inline]
+    pub fn with_colour(mut self, colour: bool) -> Self {
+        self.colour = colour;
+        self
+    }
+
+    /// Generates Ariadne [`Report`]'s for errors that point to the build script,
+    /// and a single line for those that don't,
+    /// then writes them to a [`Write`] implementor.
+    ///
+    /// This process should only produce valid UTF-8.
+    pub fn write_reports(&self, w: &mut dyn Write) -> io::Result<()> {
+        use crate::runtime::FsError::{
+            CopyError, CreateDirError, CreateFileError, FileAccessError, RemoveError,
+        };
+        use RuntimeError::{
+            CommandUnsupported, CustomCommandFailure, DependencyError, ExecutionFailure,
+            ExecutionFailureWithOutput, FailedDependency, FsError, JoinPathsError,
+        };
+
+        let source = || (self.file_name.clone(), Source::from(self.code.clone()));
+        let config = Config::default().with_color(self.colour);
+        let red = Style::new().red();
+        let cyan = Style::new().cyan();
+        let fs_single = |w: &mut dyn Write,
+                         err: &RuntimeError,
+                         p: &Spanned<String>,
+                         io: &io::Error,
+                         message: &str| {
+            Report::build(
+                ReportKind::Custom("[err]", Color::Red),
+                (self.file_name.clone(), p.span()),
+            )
+            .with_code(err.code())
+            .with_config(config)
+            .with_message(message)
+            .with_note(io.to_string())
+            .with_label(
+                ariadne::Label::new((self.file_name.clone(), p.span())).with_color(Color::Red),
+            )
+            .finish()
+            .write(source(), w)
+        };
+
+        for err in self.errors {
+            match err {
+                DependencyError(span) => {
+                    writeln!(
+                        w,
+                        "{} [{}] Failed to complete {} due to other errors",
+                        paint("[err]", red, self.colour),
+                        err.code(),
+                        paint(span, red, self.colour)
+                    )?;
+                }
+                FailedDependency(parent, child) => {
+                    writeln!(
+                        w,
+                        "{} [{}] Unable to complete unit {} due to {} failing",
+                        paint("[err]", red, self.colour),
+                        err.code(),
+                        paint(parent, cyan, self.colour),
+                        paint(child, red, self.colour)
+                    )?;
+                }
+                ExecutionFailure(args, io) | ExecutionFailureWithOutput(args, io, _) => {
+                    let output = match err {
+                        ExecutionFailureWithOutput(.., output) => Some(output),
+                        _ => None,
+                    };
+                    if !args.is_empty() {
+                        let arg0 = args.first().unwrap().span();
+                        let argn = args.last().unwrap().span();
+
+                        let mut report = Report::build(
+                            ReportKind::Error,
+                            (self.file_name.clone(), arg0.clone()),
+                        )
+                        .with_code(err.code())
+                        .with_config(config)
+                        .with_label(
+                            ariadne::Label::new((self.file_name.clone(), arg0.start..argn.end))
+                                .with_color(Color::Red),
+                        )
+                        .with_message("Failed to execute process")
+                        .with_note(io.to_string());
+                        if let Some(output) = output.filter(|o| !o.trim().is_empty()) {
+                            report = report.with_note(format!("Output:\n{}", output.trim_end()));
+                        }
+                        report.finish().write(source(), &mut *w)?;
+                    }
+                }
+                FsError(CreateFileError(p, io)) => {
+                    fs_single(w, err, p, io, "Failed to create file")?;
+                }
+                FsError(CreateDirError(p, io)) => {
+                    fs_single(w, err, p, io, "Failed to create directory")?;
+                }
+                FsError(RemoveError(p, io)) => {
+                    fs_single(w, err, p, io, "Failed to remove file/directory")?;
+                }
+                FsError(FileAccessError(p, io)) => {
+                    fs_single(w, err, p, io, "Unable to read the file contents")?;
+                }
+                FsError(CopyError(src, dst, io)) => {
+                    Report::build(
+                        ReportKind::Custom("[err]", Color::Red),
+                        (self.file_name.clone(), src.span()),
+                    )
+                    .with_code(err.code())
+                    .with_config(config)
+                    .with_message(format!(
+                        "Unable copy {} to {}",
+                        paint(src, cyan, self.colour),
+                        paint(dst, cyan, self.colour)
+                    ))
+                    .with_note(io.to_string())
+                    .with_label(
+                        ariadne::Label::new((self.file_name.clone(), src.span()))
+                            .with_color(Color::Red),
+                    )
+                    .with_label(
+                        ariadne::Label::new((self.file_name.clone(), dst.span()))
+                            .with_color(Color::Red),
+                    )
+                    .finish()
+                    .write(source(), &mut *w)?;
+                }
+                JoinPathsError(e) => {
+                    writeln!(
+                        w,
+                        "{} [{}] Error occured when join to PATH: {e}",
+                        paint("[err]", red, self.colour),
+                        err.code()
+                    )?;
+                }
+                CommandUnsupported(cmd) => {
+                    writeln!(
+                        w,
+                        "{} [{}] Command {cmd:?} is unsupported on the current runtime",
+                        paint("[err]", red, self.colour),
+                        err.code()
+                    )?;
+                }
+                CustomCommandFailure(module, name, e) => {
+                    let span = module.span().start..name.span().end;
+                    Report::build(ReportKind::Error, (self.file_name.clone(), span.clone()))
+                        .with_code(err.code())
+                        .with_config(config)
+                        .with_message(format!(
+                            "{} failed",
+                            paint(format!("{module}::{name}"), cyan, self.colour)
+                        ))
+                        .with_note(e.to_string())
+                        .with_label(
+                            ariadne::Label::new((self.file_name.clone(), span))
+                                .with_color(Color::Red),
+                        )
+                        .finish()
+                        .write(source(), &mut *w)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Plain text versions of the errors that point to the build script.
+    ///
+    /// Processes that failed are shown as they're written in the build script,
+    /// rather than as a list of arguments.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.errors
+            .iter()
+            .filter_map(|e| {
+                let message = match e {
+                    RuntimeError::ExecutionFailure(args, io)
+                    | RuntimeError::ExecutionFailureWithOutput(args, io, _) => {
+                        let args = args.iter().map(Spanned::inner).cloned().collect::<Vec<_>>();
+                        format!("Failed to execute {}: {io}", args.join(" "))
+                    }
+                    _ => e.to_string(),
+                };
+                Some(Diagnostic::error(e.span()?, message).with_code(e.code()))
+            })
+            .collect()
+    }
+}
+
+impl Diagnostics {
+    /// Writes the reports of every error, as done by [`ParserErrorFormatter`] and [`LowerErrorFormatter`],
+    /// coloured if `colour` is set.
+    ///
+    /// This process should only produce valid UTF-8.
+    pub fn write_reports(&self, w: &mut dyn Write, colour: bool) -> io::Result<()> {
+        match &self.errors {
+            ParseErrors::Io(_) => {
+                writeln!(w, "{} {self}", paint("[err]", Style::new().red(), colour))
+            }
+            ParseErrors::Lexer(err) => {
+                ParserErrorFormatter::new(err.clone(), &self.name, &self.code)
+                    .with_colour(colour)
+                    .write_reports(w)
+            }
+            ParseErrors::Parser(err) => {
+                ParserErrorFormatter::new(err.clone(), &self.name, &self.code)
+                    .with_colour(colour)
+                    .write_reports(w)
+            }
+            ParseErrors::Lower(err) => {
+                LowerErrorFormatter::new(err.clone(), &self.name, &self.code)
+                    .with_colour(colour)
+                    .write_reports(w)
+            }
+        }
+    }
+
+    /// Plain text versions of each error's report,
+    /// empty if the build script couldn't be read.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match &self.errors {
+            ParseErrors::Io(_) => Vec::new(),
+            ParseErrors::Lexer(err) => {
+                ParserErrorFormatter::new(err.clone(), &self.name, &self.code).diagnostics()
+            }
+            ParseErrors::Parser(err) => {
+                ParserErrorFormatter::new(err.clone(), &self.name, &self.code).diagnostics()
+            }
+            ParseErrors::Lower(err) => {
+                LowerErrorFormatter::new(err.clone(), &self.name, &self.code).diagnostics()
+            }
+        }
+    }
+}
+
+fn write_diagnostics(
+    diagnostics: &[Diagnostic],
+    file_name: &str,
+    code: &str,
+    colour: bool,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    for d in diagnostics {
+        d.write_report(file_name, code, colour, &mut *w)?;
+        writeln!(&mut *w)?;
+    }
+
+    Ok(())
+}
+
+/// Styles `text` only if `colour` is set.
+fn paint<T: Display>(text: T, style: Style, colour: bool) -> Styled<T> {
+    if colour { style } else { Style::new() }.style(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_str;
+
+    #[test]
+    fn suggests_identifier_before_error() {
+        let code = "unit Build {\n    dependson(Test)\n}\n\nunit Test {}\n";
+        let diagnostics = parse_str("main.august", code).unwrap_err().diagnostics();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].help.as_deref(),
+            Some("Did you mean depends_on?")
+        );
+    }
+
+    #[test]
+    fn reports_every_syntax_error() {
+        let code =
+            "unit A {\n    exec(foo\n}\n\nunit B {\n    fs::copy(\"a\")\n}\n\nexpose B as build\n";
+        let diagnostics = parse_str("main.august", code).unwrap_err().diagnostics();
+
+        let lines = diagnostics
+            .iter()
+            .map(|d| code[..d.span.start].matches('\n').count() + 1)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [3, 6]);
+    }
+
+    #[test]
+    fn doesnt_suggest_what_was_found() {
+        let diagnostics = parse_str("main.august", "expose as build\n")
+            .unwrap_err()
+            .diagnostics();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].help, None);
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    io::{self, Write},
+    ops::Range,
+};
+
+use ariadne::{Color, Config, Report, ReportKind, Source};
+use serde_json::{json, Value};
+
+/// A plain text version of an error report,
+/// for output read by other tools rather than people.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    /// Part of the build script the diagnostic is about
+    pub span: Range<usize>,
+    /// Describes what's at `span`
+    pub label: Option<String>,
+    /// Other parts of the build script that are relevant
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(span: Range<usize>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code: None,
+            message: message.into(),
+            span,
+            label: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    #[inline]
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Describes the part of the build script the diagnostic is about.
+    #[inline]
+    pub fn with_primary_label(mut self, message: impl Into<String>) -> Self {
+        self.label = Some(message.into());
+        self
+    }
+
+    #[inline]
+    pub fn with_label(mut self, span: Range<usize>, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    #[inline]
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    #[inline]
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Writes the diagnostic as a JSON object,
+    /// with line and column numbers worked out from `code`.
+    pub fn to_json(&self, file_name: &str, code: &str) -> Value {
+        json!({
+            "severity": match self.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            },
+            "code": self.code,
+            "message": self.message,
+            "file": file_name,
+            "span": span_json(&self.span, code),
+            "label": self.label,
+            "labels": self.labels.iter().map(|l| json!({
+                "span": span_json(&l.span, code),
+                "message": l.message,
+            })).collect::<Vec<_>>(),
+            "notes": self.notes,
+            "help": self.help,
+        })
+    }
+
+    /// Writes the diagnostic as an Ariadne [`Report`] pointing into `code`,
+    /// coloured if `colour` is set.
+    pub fn write_report(
+        &self,
+        file_name: &str,
+        code: &str,
+        colour: bool,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        let (kind, primary_colour) = match self.severity {
+            Severity::Error => (ReportKind::Error, Color::Red),
+            Severity::Warning => (ReportKind::Warning, Color::Yellow),
+        };
+
+        let mut primary = ariadne::Label::new((file_name.to_owned(), self.span.clone()))
+            .with_color(primary_colour);
+        if let Some(label) = &self.label {
+            primary = primary.with_message(label);
+        }
+        let mut report = Report::build(kind, (file_name.to_owned(), self.span.clone()))
+            .with_config(Config::default().with_color(colour))
+            .with_message(&self.message)
+            .with_label(primary)
+            .with_labels(self.labels.iter().map(|l| {
+                ariadne::Label::new((file_name.to_owned(), l.span.clone()))
+                    .with_message(&l.message)
+                    .with_color(Color::Cyan)
+            }));
+        report.with_notes(&self.notes);
+        if let Some(code) = self.code {
+            report = report.with_code(code);
+        }
+        if let Some(help) = &self.help {
+            report = report.with_help(help);
+        }
+
+        report
+            .finish()
+            .write((file_name.to_owned(), Source::from(code)), w)
+    }
+}
+
+/// Start and end of a span as lines and columns, starting from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Position {
+    /// Works out the position of a span of chars in `code`.
+    /// Whitespace picked up by the lexer at either end of the span is skipped.
+    pub fn of(span: &Range<usize>, code: &str) -> Self {
+        let chars = code.chars().collect::<Vec<_>>();
+        let mut start = span.start.min(chars.len());
+        let mut end = span.end.min(chars.len());
+        while start < end && chars[start].is_whitespace() {
+            start += 1;
+        }
+        while end > start && chars[end - 1].is_whitespace() {
+            end -= 1;
+        }
+
+        let line_col = |offset: usize| {
+            let before = &chars[..offset];
+            let line = before.iter().filter(|c| **c == '\n').count() + 1;
+            let col = offset - before.iter().rposition(|c| *c == '\n').map_or(0, |i| i + 1) + 1;
+            (line, col)
+        };
+        let (line, column) = line_col(start);
+        let (end_line, end_column) = line_col(end);
+
+        Self {
+            line,
+            column,
+            end_line,
+            end_column,
+        }
+    }
+}
+
+fn span_json(span: &Range<usize>, code: &str) -> Value {
+    let pos = Position::of(span, code);
+    json!({
+        "start": span.start,
+        "end": span.end,
+        "line": pos.line,
+        "column": pos.column,
+        "end_line": pos.end_line,
+        "end_column": pos.end_column,
+    })
+}
@@ -0,0 +1,439 @@
+/// Long form explanation of an error code, shown by `august explain`.
+pub fn explanation(code: &str) -> Option<&'static str> {
+    let text = match code.to_ascii_uppercase().as_str() {
+        "A0001" => {
+            r#"Unexpected input
+
+The build script contains a character or token that isn't valid where it was found.
+The report points to what was found and lists what would have been accepted instead.
+
+    unit Build {
+        exec cargo build
+    }
+
+Commands take their arguments in brackets:
+
+    unit Build {
+        exec(cargo build)
+    }"#
+        }
+        "A0002" => {
+            r#"Unclosed delimiter
+
+A bracket or brace was opened but another delimiter was reached before it was closed.
+The report points to both the opening delimiter and where it needed to be closed."#
+        }
+        "A0003" => {
+            r#"Invalid syntax
+
+The build script couldn't be parsed for a reason given in the message of the report."#
+        }
+        "A0101" => {
+            r#"Duplicate expose
+
+Each pragma can only have one unit exposed to it.
+
+    expose Build as build
+    expose Release as build
+
+Remove one of the expose statements or expose the unit to a different pragma:
+
+    expose Build as build
+    expose Test as test"#
+        }
+        "A0102" => {
+            r#"Duplicate unit
+
+Two units were defined with the same name.
+Units are referred to by name, so every name has to be unique.
+
+    unit Build {
+        exec(cargo build)
+    }
+
+    unit Build {
+        exec(cargo build --release)
+    }
+
+Rename or remove one of the units:
+
+    unit Build {
+        exec(cargo build)
+    }
+
+    unit Release {
+        exec(cargo build --release)
+    }"#
+        }
+        "A0103" => {
+            r#"Duplicate dependency
+
+A unit lists the same dependency more than once.
+Dependencies only run once, so the repeated entry has no effect.
+
+    unit Test {
+        depends_on(Build, Build)
+    }
+
+Remove the repeated dependency:
+
+    unit Test {
+        depends_on(Build)
+    }"#
+        }
+        "A0104" => {
+            r#"Duplicate meta item
+
+A unit sets the same meta item more than once, so it isn't clear which value to use.
+
+    unit Build {
+        meta(
+            @desc "Builds the project"
+            @desc "Compiles the project"
+        )
+    }
+
+Keep only one of the values:
+
+    unit Build {
+        meta(
+            @desc "Builds the project"
+        )
+    }"#
+        }
+        "A0105" => {
+            r#"Invalid meta value
+
+A meta item that August reads has a value it doesn't understand.
+For example, `@output` only accepts `inherit`, `prefixed`, `grouped` or `quiet-success`.
+
+    unit Build {
+        meta(
+            @output "quiet"
+        )
+    }
+
+Use one of the accepted values:
+
+    unit Build {
+        meta(
+            @output "quiet-success"
+        )
+    }"#
+        }
+        "A0106" => {
+            r#"Unknown unit
+
+A unit name used in `expose`, `depends_on` or `do` doesn't match any unit in the script.
+This is usually caused by a typo, as unit names are case sensitive.
+If there is a unit with a similar name, the report suggests it.
+
+    unit Test {
+        depends_on(build)
+    }
+
+    unit Build {
+        exec(cargo build)
+    }
+
+Refer to the unit by its exact name, or define the missing unit:
+
+    unit Test {
+        depends_on(Build)
+    }"#
+        }
+        "A0107" => {
+            r#"Unknown command
+
+A command called as `module::command(...)` isn't one of August's built-in commands
+and wasn't registered by the program running the build script.
+Custom commands are only available when August is used as a library,
+which registers them with a `CommandRegistry`.
+
+    unit Image {
+        docker::biuld("app")
+    }
+
+Check the spelling of the module and command, the report suggests a registered command
+if there is one with a similar name:
+
+    unit Image {
+        docker::build("app")
+    }"#
+        }
+        "A0108" => {
+            r#"Call that needs a held lock
+
+A unit with `@lock` calls a unit with `do` that needs the same lock,
+either itself or through its dependencies and the units it calls.
+The caller holds the lock until its commands finish,
+so the called unit would wait on it forever.
+
+    unit Migrate {
+        meta(@lock "database")
+        do(Seed)
+    }
+
+    unit Seed {
+        meta(@lock "database")
+        exec("./seed.sh")
+    }
+
+Dependencies run before the lock is taken,
+so depend on the unit instead of calling it:
+
+    unit Migrate {
+        meta(@lock "database")
+        depends_on(Seed)
+    }"#
+        }
+        "A0109" => {
+            r#"Locks taken in a different order
+
+A unit with `@lock` calls a unit with `do` that takes another lock,
+while other units hold that lock when they lead to the first one being taken.
+If both run at the same time, each waits on the lock the other holds forever.
+
+    unit Root {
+        depends_on(Migrate, Backup)
+    }
+
+    unit Migrate {
+        meta(@lock "database")
+        do(Upload)
+    }
+
+    unit Backup {
+        meta(@lock "storage")
+        do(Dump)
+    }
+
+    unit Upload {
+        meta(@lock "storage")
+        exec("./upload.sh")
+    }
+
+    unit Dump {
+        meta(@lock "database")
+        exec("./dump.sh")
+    }
+
+Always take locks in the same order,
+or depend on the unit instead of calling it so its lock is taken beforehand:
+
+    unit Migrate {
+        meta(@lock "database")
+        depends_on(Upload)
+    }"#
+        }
+        "A0201" => {
+            r#"Dependency failed
+
+A unit wasn't run because one of its dependencies failed.
+The errors of the dependency are reported separately, fix those first."#
+        }
+        "A0202" => {
+            r#"Dependency failed while waiting
+
+A unit was waiting on a dependency that was already being run by another unit,
+and the dependency failed.
+The errors of the dependency are reported separately, fix those first."#
+        }
+        "A0203" => {
+            r#"Execution failure
+
+A process started with `exec` couldn't be started or exited unsuccessfully.
+
+If it couldn't be started, check that the program is installed and can be found on `PATH`,
+which can be changed with `env::path_push`.
+
+    unit Build {
+        exec(carg build)
+    }
+
+If it exited unsuccessfully, the process itself reported an error.
+Its output is shown above the report, or in the report with `--output quiet-success`."#
+        }
+        "A0204" => {
+            r#"Invalid PATH
+
+A path added with `env::path_push` couldn't be joined onto `PATH`,
+usually because it contains the path separator (`:` on Unix, `;` on Windows).
+
+    unit Build {
+        env::path_push("tools:bin")
+    }
+
+Add each directory separately:
+
+    unit Build {
+        env::path_push("tools")
+        env::path_push("bin")
+    }"#
+        }
+        "A0205" => {
+            r#"Unsupported command
+
+The runtime doesn't know how to run a command in the build script.
+This can happen when a build script written for a newer version of August
+is run with an older one,
+or when a custom command was lowered with a registry the runtime wasn't given."#
+        }
+        "A0206" => {
+            r#"Custom command failed
+
+A custom command registered by the program running the build script returned an error.
+The report includes the error, check the documentation of the program for what it means."#
+        }
+        "A0301" => {
+            r#"Failed to create file
+
+`fs::create` couldn't create a file, usually because its parent directory doesn't exist
+or permission was denied.
+
+    unit Setup {
+        fs::create("out/build.log")
+    }
+
+Create the parent directory first:
+
+    unit Setup {
+        fs::create_dir("out")
+        fs::create("out/build.log")
+    }"#
+        }
+        "A0302" => {
+            r#"Failed to create directory
+
+`fs::create_dir` couldn't create a directory, usually because permission was denied
+or a file already exists at that path."#
+        }
+        "A0303" => {
+            r#"Failed to remove
+
+`fs::remove` couldn't remove a file or directory, usually because it doesn't exist
+or permission was denied.
+
+Paths are relative to the directory of the build script."#
+        }
+        "A0304" => {
+            r#"Failed to read file
+
+`fs::print_file` or `fs::eprint_file` couldn't read a file,
+usually because it doesn't exist or isn't valid UTF-8.
+
+Paths are relative to the directory of the build script."#
+        }
+        "A0305" => {
+            r#"Failed to copy or move
+
+`fs::copy` or `fs::move` couldn't copy the source to the destination,
+usually because the source doesn't exist or the destination's parent directory is missing.
+
+    unit Package {
+        fs::copy("target/release/app", "dist/app")
+    }
+
+Create the destination directory first:
+
+    unit Package {
+        fs::create_dir("dist")
+        fs::copy("target/release/app", "dist/app")
+    }"#
+        }
+        "A0401" => {
+            r#"Unreachable unit (lint: unreachable-unit)
+
+A unit isn't exposed and no exposed unit depends on it or calls it with `do`,
+directly or through other units, so it can only be run with `august run`.
+
+    expose Build as build
+
+    unit Build {
+        exec(cargo build)
+    }
+
+    unit Old {
+        exec(make)
+    }
+
+Remove the unit if it's no longer needed,
+or allow the lint with `august check -A unreachable-unit` if it's meant to be run directly."#
+        }
+        "A0402" => {
+            r#"Dependency also called with do (lint: depends-on-and-do)
+
+A unit both depends on another unit and calls it with `do`.
+The unit runs once as a dependency before any commands,
+then again when `do` is reached, which is rarely intended.
+
+    unit Test {
+        depends_on(Build)
+        do(Build)
+    }
+
+Keep only one of them, unless the unit is meant to run twice:
+
+    unit Test {
+        depends_on(Build)
+    }"#
+        }
+        "A0403" => {
+            r#"Empty unit (lint: empty-unit)
+
+A unit has no commands and no dependencies, so running it does nothing.
+
+    unit Lint {
+    }
+
+Add commands or dependencies to the unit, or remove it."#
+        }
+        "A0404" => {
+            r#"Concurrent block with one command (lint: single-concurrent)
+
+A `concurrent` block only contains one command, so there is nothing for it to run alongside.
+
+    unit Build {
+        concurrent {
+            exec(cargo build)
+        }
+    }
+
+Move the command out of the block:
+
+    unit Build {
+        exec(cargo build)
+    }"#
+        }
+        "A0405" => {
+            r#"Removing outside the script directory (lint: remove-outside-script)
+
+`fs::remove` is given an absolute path or one that uses `..` to leave the directory of the build script.
+Removing files outside of the project is rarely intended and can't be undone.
+
+    unit Clean {
+        fs::remove("../target")
+    }
+
+Check the path is correct, or allow the lint with `august check -A remove-outside-script`."#
+        }
+        "A0406" => {
+            r#"Unknown meta item (lint: unknown-meta)
+
+A unit sets a meta item that August doesn't use.
+This is often a typo of a meta item that changes how the unit is run.
+
+    unit Build {
+        meta(
+            @ouptut "grouped"
+        )
+    }
+
+Known meta items are `name`, `desc`, `description`, `deps`, `calls`, `output` and `lock`.
+Allow the lint with `august check -A unknown-meta` if custom meta items are intended."#
+        }
+        _ => return None,
+    };
+
+    Some(text)
+}
@@ -0,0 +1,202 @@
+//! Writing build scripts back out in a consistent style, used by `august fmt`.
+//!
+//! Build scripts can't contain comments,
+//! so the parsed [`AST`] holds everything needed to write the script again.
+
+use std::fmt::Write;
+
+use crate::{parser::AST, Command, Pragma, Quoted};
+
+const INDENT: &str = "    ";
+
+/// Writes a parsed build script in the canonical style.
+///
+/// - Exposes come first, followed by each unit separated by a blank line.
+/// - A unit's `meta` and `depends_on` are merged and moved to the top of the unit,
+///   with `meta` items sorted by name.
+/// - `~` and `@` are written as `exec` and `meta`, and commands always include their module.
+/// - Blocks are indented with four spaces.
+pub fn format(ast: &[AST]) -> String {
+    let mut out = String::new();
+
+    for item in ast {
+        if let AST::Expose(pragma, unit) = item {
+            let pragma = match pragma {
+                Pragma::Test => "test",
+                Pragma::Build => "build",
+            };
+            writeln!(out, "expose {unit} as {pragma}").unwrap();
+        }
+    }
+
+    for item in ast {
+        if let AST::Unit(name, cmds) = item {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            unit(&mut out, name.inner(), cmds);
+        }
+    }
+
+    out
+}
+
+fn unit(out: &mut String, name: &str, cmds: &[Command]) {
+    if cmds.is_empty() {
+        writeln!(out, "unit {name} {{}}").unwrap();
+        return;
+    }
+    writeln!(out, "unit {name} {{").unwrap();
+
+    let mut meta = cmds
+        .iter()
+        .filter_map(|c| match c {
+            Command::Meta(items) => Some(items),
+            _ => None,
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    meta.sort_by(|(a, _), (b, _)| a.inner().cmp(b.inner()));
+    let deps = cmds
+        .iter()
+        .filter_map(|c| match c {
+            Command::DependsOn(deps) => Some(deps),
+            _ => None,
+        })
+        .flatten()
+        .map(|d| d.inner().as_str())
+        .collect::<Vec<_>>();
+    let rest = cmds
+        .iter()
+        .filter(|c| !matches!(c, Command::Meta(_) | Command::DependsOn(_)))
+        .collect::<Vec<_>>();
+
+    let header = !meta.is_empty() || !deps.is_empty();
+
+    if !meta.is_empty() {
+        writeln!(out, "{INDENT}meta(").unwrap();
+        for (var, val) in meta {
+            writeln!(out, "{INDENT}{INDENT}@{var} {}", Quoted(val)).unwrap();
+        }
+        writeln!(out, "{INDENT})").unwrap();
+    }
+    if !deps.is_empty() {
+        writeln!(out, "{INDENT}depends_on({})", deps.join(", ")).unwrap();
+    }
+    if header && !rest.is_empty() {
+        out.push('\n');
+    }
+
+    for cmd in rest {
+        command(out, cmd, 1);
+    }
+
+    out.push_str("}\n");
+}
+
+fn command(out: &mut String, cmd: &Command, depth: usize) {
+    let indent = INDENT.repeat(depth);
+
+    match cmd {
+        Command::Concurrent(cmds) if cmds.is_empty() => {
+            writeln!(out, "{indent}concurrent {{}}").unwrap();
+        }
+        Command::Concurrent(cmds) => {
+            writeln!(out, "{indent}concurrent {{").unwrap();
+            for cmd in cmds {
+                command(out, cmd, depth + 1);
+            }
+            writeln!(out, "{indent}}}").unwrap();
+        }
+        cmd => writeln!(out, "{indent}{cmd}").unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format;
+    use crate::{parse_ast, Module};
+
+    const MESSY: &str = r#"unit Build { ~(cargo build --release) depends_on(Test)
+@(@name "Build" @desc "Says \"hi\"\tand\\leaves")
+concurrent { io::println("a\nb") fs::copy("src", "dist") }
+do(Lints) env::set_var("KEY", "value") depends_on(Lints) }
+expose Build as build
+unit Test{ exec("./run tests" -v) }
+unit Lints {}
+expose Test as test
+"#;
+
+    fn fmt(code: &str) -> String {
+        format(&parse_ast("main.august", code).unwrap())
+    }
+
+    fn lower(code: &str) -> Module {
+        Module::lower(parse_ast("main.august", code).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn writes_the_canonical_style() {
+        assert_eq!(
+            fmt(MESSY),
+            r#"expose Build as build
+expose Test as test
+
+unit Build {
+    meta(
+        @desc "Says \"hi\"\tand\\leaves"
+        @name "Build"
+    )
+    depends_on(Test, Lints)
+
+    exec(cargo build --release)
+    concurrent {
+        io::println("a\nb")
+        fs::copy("src", "dist")
+    }
+    do(Lints)
+    env::set_var("KEY", "value")
+}
+
+unit Test {
+    exec("./run tests" -v)
+}
+
+unit Lints {}
+"#
+        );
+    }
+
+    #[test]
+    fn is_idempotent() {
+        for code in [
+            MESSY,
+            include_str!("../syntax-examples/main.august"),
+            include_str!("../syntax-examples/new_syntax.august"),
+        ] {
+            let once = fmt(code);
+            assert_eq!(fmt(&once), once);
+        }
+    }
+
+    #[test]
+    fn keeps_the_meaning_of_the_script() {
+        for code in [
+            MESSY,
+            include_str!("../syntax-examples/main.august"),
+            include_str!("../syntax-examples/new_syntax.august"),
+        ] {
+            let before = lower(code);
+            let after = lower(&fmt(code));
+            assert_eq!(after.expose, before.expose);
+            assert_eq!(after.units, before.units);
+        }
+    }
+
+    #[test]
+    fn keeps_the_ast_of_canonical_scripts() {
+        let code = fmt(MESSY);
+        let ast = parse_ast("main.august", &code).unwrap();
+        assert_eq!(parse_ast("main.august", &format(&ast)).unwrap(), ast);
+    }
+}
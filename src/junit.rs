@@ -0,0 +1,122 @@
+use std::{io::Write, time::Duration};
+
+use august_build::{
+    parser::Spanned,
+    runtime::{RunReport, UnitReport, UnitStatus},
+    Module,
+};
+
+/// Writes a run as a JUnit XML report with a `<testcase>` for each unit that ran.
+///
+/// Test cases are named with the unit's `@name` meta item if it has one.
+/// Failed units include their error and the output of their `exec` calls,
+/// which is only available if the runtime captured it.
+pub fn write_junit(
+    report: &RunReport,
+    module: &Module,
+    suite: &str,
+    w: &mut dyn Write,
+) -> std::io::Result<()> {
+    let units = report
+        .units
+        .iter()
+        .filter(|u| u.status != UnitStatus::Cached)
+        .collect::<Vec<_>>();
+    let count = |status: UnitStatus| units.iter().filter(|u| u.status == status).count();
+    let failures = count(UnitStatus::Failed);
+    let skipped = count(UnitStatus::Skipped);
+    let time = seconds(report.duration());
+
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        w,
+        r#"<testsuites name="august" tests="{}" failures="{failures}" errors="0" skipped="{skipped}" time="{time}">"#,
+        units.len()
+    )?;
+    writeln!(
+        w,
+        r#"  <testsuite name="{}" tests="{}" failures="{failures}" errors="0" skipped="{skipped}" time="{time}">"#,
+        escape(suite),
+        units.len()
+    )?;
+
+    for unit in units {
+        let name = module
+            .units()
+            .get(&Spanned::new(unit.name.clone()))
+            .and_then(|u| u.display_name())
+            .unwrap_or(&unit.name);
+        write!(
+            w,
+            r#"    <testcase name="{}" classname="{}" time="{}""#,
+            escape(name),
+            escape(suite),
+            seconds(unit.duration())
+        )?;
+
+        let error = unit.error.as_deref().unwrap_or_default();
+        let output = output(unit);
+        match unit.status {
+            UnitStatus::Completed if output.is_empty() => {
+                writeln!(w, "/>")?;
+                continue;
+            }
+            UnitStatus::Completed => {
+                writeln!(w, ">")?;
+            }
+            UnitStatus::Failed => {
+                writeln!(w, ">")?;
+                writeln!(
+                    w,
+                    r#"      <failure message="{}">{}</failure>"#,
+                    escape(error),
+                    escape(&format!("{error}\n\n{output}"))
+                )?;
+            }
+            UnitStatus::Skipped => {
+                writeln!(w, ">")?;
+                writeln!(w, r#"      <skipped message="{}"/>"#, escape(error))?;
+            }
+            UnitStatus::Cached => unreachable!("cached units are filtered out"),
+        }
+        // Already part of the failure
+        if !output.is_empty() && unit.status != UnitStatus::Failed {
+            writeln!(w, "      <system-out>{}</system-out>", escape(&output))?;
+        }
+        writeln!(w, "    </testcase>")?;
+    }
+
+    writeln!(w, "  </testsuite>")?;
+    writeln!(w, "</testsuites>")
+}
+
+/// Output of the unit's `exec` calls in the order they finished.
+fn output(unit: &UnitReport) -> String {
+    unit.commands
+        .iter()
+        .filter_map(|c| c.output.as_deref())
+        .collect()
+}
+
+fn seconds(d: Duration) -> String {
+    format!("{:.3}", d.as_secs_f64())
+}
+
+/// Escapes text for use in XML attributes and content,
+/// dropping control characters that XML 1.0 doesn't allow.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\n' | '\r' | '\t' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -67,6 +67,10 @@ impl Display for Token {
     }
 }
 
+/// Splits a build script into tokens.
+///
+/// Spans include the whitespace around each token.
+/// Use [`crate::cst::Cst`] for spans of just the tokens, along with the whitespace between them.
 pub fn lexer() -> impl Parser<char, Vec<(Token, Range<usize>)>, Error = Simple<char>> {
     let escape = just('\\').ignore_then(
         just('\\')
@@ -107,13 +111,10 @@ pub fn lexer() -> impl Parser<char, Vec<(Token, Range<usize>)>, Error = Simple<c
         just('}').to(Token::CloseDelim(Delim::Curly)),
     ));
 
-    let raw_ident = filter(|c: &char| {
-        matches!(c.to_char(),
-        '!'..='&' | '*'..='+' | '-'..='.' | '0'..=';' | '=' | '?'..='Z' | '^'..='z' | '|')
-    })
-    .repeated()
-    .at_least(1)
-    .collect::<String>();
+    let raw_ident = filter(|c: &char| is_raw_ident_char(c.to_char()))
+        .repeated()
+        .at_least(1)
+        .collect::<String>();
 
     let token = choice((
         keywords,
@@ -139,3 +140,9 @@ pub fn lexer() -> impl Parser<char, Vec<(Token, Range<usize>)>, Error = Simple<c
         .then_ignore(end())
         .labelled("tokens")
 }
+
+/// Characters that can be part of a [`Token::RawIdent`].
+pub(crate) fn is_raw_ident_char(c: char) -> bool {
+    matches!(c,
+        '!'..='&' | '*'..='+' | '-'..='.' | '0'..=';' | '=' | '?'..='Z' | '^'..='z' | '|')
+}
@@ -1,31 +1,142 @@
-/// When using August as a library,
-/// disable default features so Cargo doesn't pull CLI dependencies
-///
-/// ```toml
-/// [dependencies]
-/// august-build = { version = "*", default-features = false }
-/// ```
+//! When using August as a library,
+//! disable default features so Cargo doesn't pull CLI dependencies
+//!
+//! ```toml
+//! [dependencies]
+//! august-build = { version = "*", default-features = false }
+//! ```
+//!
+//! Enable the `diagnostics` feature for the same error reports as the CLI
+//!
+//! ```toml
+//! [dependencies]
+//! august-build = { version = "*", default-features = false, features = ["diagnostics"] }
+//! ```
+//!
+//! Enable the `serde` feature to serialize and deserialize lowered modules.
+//! Deserialized modules are checked again like [`Module::lower`],
+//! except for custom commands, which fail when they're run if they aren't registered.
+
+use std::{
+    fmt::{Display, Write},
+    hash::Hash,
+    io,
+    ops::Range,
+    path::Path,
+};
+
+use chumsky::{error::SimpleReason, prelude::Simple};
 use indexmap::{IndexMap, IndexSet};
 use rustc_hash::FxBuildHasher;
 use thiserror::Error;
 
-use parser::{Spanned, AST};
+use cst::Cst;
+use lexer::Token;
+use parser::{Spanned, AST, MODULES};
+use registry::CommandRegistry;
+use runtime::OutputMode;
 
+pub mod builder;
+#[cfg(feature = "diagnostics")]
+pub mod colours;
+pub mod cst;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+pub mod fmt;
 pub mod lexer;
+pub mod lint;
 pub mod parser;
+pub mod registry;
 pub mod runtime;
+pub mod suggest;
 
 pub(crate) type HashMap<K, V> = IndexMap<K, V, FxBuildHasher>;
 pub(crate) type HashSet<K> = IndexSet<K, FxBuildHasher>;
 
+/// Serializes maps keyed by [`Spanned`] as a list of `[key, value]` pairs,
+/// since formats like JSON only allow strings as keys.
+#[cfg(feature = "serde")]
+mod entries {
+    use std::hash::Hash;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::HashMap;
+
+    pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map)
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Hash + Eq,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Vec::<(K, V)>::deserialize(deserializer).map(|entries| entries.into_iter().collect())
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "ModuleRepr"))]
 pub struct Module {
     expose: HashMap<Pragma, Spanned<String>>,
+    #[cfg_attr(feature = "serde", serde(with = "entries"))]
+    units: HashMap<Spanned<String>, Unit>,
+}
+
+/// How [`Module`] is deserialized, before it's checked again.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "Module")]
+struct ModuleRepr {
+    expose: HashMap<Pragma, Spanned<String>>,
+    #[serde(with = "entries")]
     units: HashMap<Spanned<String>, Unit>,
 }
 
+#[cfg(feature = "serde")]
+impl TryFrom<ModuleRepr> for Module {
+    type Error = String;
+
+    fn try_from(repr: ModuleRepr) -> Result<Self, Self::Error> {
+        let ast = repr
+            .expose
+            .into_iter()
+            .map(|(pragma, unit)| AST::Expose(pragma, unit))
+            .chain(
+                repr.units
+                    .into_iter()
+                    .map(|(name, unit)| AST::Unit(name, unit.into_commands())),
+            )
+            .collect();
+
+        Self::lower_checking(ast, None).map_err(|errors| join_errors(&errors))
+    }
+}
+
 impl Module {
+    /// Lowers a parsed build script, which can't call any custom commands.
     pub fn lower(ast: Vec<AST>) -> Result<Self, Vec<LowerError>> {
+        Self::lower_with(ast, &CommandRegistry::default())
+    }
+
+    /// Lowers a parsed build script, checking that custom commands are in `registry`.
+    pub fn lower_with(ast: Vec<AST>, registry: &CommandRegistry) -> Result<Self, Vec<LowerError>> {
+        Self::lower_checking(ast, Some(registry))
+    }
+
+    /// Lowers a parsed build script, only checking custom commands if there's a `registry`.
+    fn lower_checking(
+        ast: Vec<AST>,
+        registry: Option<&CommandRegistry>,
+    ) -> Result<Self, Vec<LowerError>> {
         let mut errors = Vec::new();
 
         let mut unit_iter = ast.iter().filter(|a| matches!(a, AST::Unit(_, _)));
@@ -63,7 +174,10 @@ impl Module {
             }
             if !units.contains_key(&unit) {
                 err = true;
-                errors.push(LowerError::NameError(unit.clone()));
+                errors.push(LowerError::NameError(
+                    unit.clone(),
+                    closest_unit(&units, &unit),
+                ));
             }
             if err {
                 continue;
@@ -75,20 +189,58 @@ impl Module {
         for unit in units.values() {
             for u in &unit.depends_on {
                 if !units.contains_key(u) {
-                    errors.push(LowerError::NameError(u.clone()));
+                    errors.push(LowerError::NameError(u.clone(), closest_unit(&units, u)));
                 }
             }
 
-            let mut dos_iter = unit.commands.iter().filter(|c| matches!(c, Command::Do(_)));
-            while let Some(Command::Do(dos)) = dos_iter.next() {
-                for d in dos {
-                    if !units.contains_key(d) {
-                        errors.push(LowerError::NameError(d.clone()));
+            for d in unit.calls() {
+                if !units.contains_key(d) {
+                    errors.push(LowerError::NameError(d.clone(), closest_unit(&units, d)));
+                }
+            }
+
+            if let Some(registry) = registry {
+                for (module, name) in unit.custom_commands() {
+                    if !registry.contains(module.inner(), name.inner()) {
+                        errors.push(LowerError::UnknownCommand(
+                            module.clone(),
+                            name.clone(),
+                            closest_command(registry, module, name),
+                        ));
                     }
                 }
             }
         }
 
+        // Locks taken through `do` while the caller holds its own lock
+        let mut lock_order = Vec::new();
+        for unit in units.values() {
+            if let Some(held) = unit.lock() {
+                for call in unit.calls() {
+                    for (taken, taker) in lock_takers(&units, call) {
+                        lock_order.push((held, call, taken, taker));
+                    }
+                }
+            }
+        }
+
+        for &(held, call, taken, taker) in &lock_order {
+            if held == taken {
+                errors.push(LowerError::LockDeadlock(
+                    call.clone(),
+                    taker.clone(),
+                    held.to_owned(),
+                ));
+            } else if lock_reaches(&lock_order, taken, held) {
+                errors.push(LowerError::LockOrder(
+                    call.clone(),
+                    taker.clone(),
+                    held.to_owned(),
+                    taken.to_owned(),
+                ));
+            }
+        }
+
         if !errors.is_empty() {
             return Err(errors);
         }
@@ -108,6 +260,172 @@ impl Module {
     }
 }
 
+/// Lexes, parses and lowers a build script.
+///
+/// `name` refers to the build script in error reports, usually it's the path of the script.
+pub fn parse_str(name: impl Into<String>, code: &str) -> Result<Module, Diagnostics> {
+    parse_str_with(name, code, &CommandRegistry::default())
+}
+
+/// Same as [`parse_str`], allowing the custom commands in `registry`.
+pub fn parse_str_with(
+    name: impl Into<String>,
+    code: &str,
+    registry: &CommandRegistry,
+) -> Result<Module, Diagnostics> {
+    let name = name.into();
+    let ast = parse_ast(&name, code)?;
+    Module::lower_with(ast, registry).map_err(|err| Diagnostics {
+        name,
+        code: code.to_owned(),
+        errors: ParseErrors::Lower(err),
+    })
+}
+
+/// Lexes and parses a build script without lowering it,
+/// such as for writing it back out with [`fmt::format`].
+pub fn parse_ast(name: impl Into<String>, code: &str) -> Result<Vec<AST>, Diagnostics> {
+    let cst = Cst::parse(code);
+    let errors = if cst.errors().is_empty() {
+        match cst.ast() {
+            Ok(ast) => return Ok(ast),
+            Err(err) => ParseErrors::Parser(err),
+        }
+    } else {
+        ParseErrors::Lexer(cst.errors().to_vec())
+    };
+
+    Err(Diagnostics {
+        name: name.into(),
+        code: code.to_owned(),
+        errors,
+    })
+}
+
+/// Reads a build script and parses it with [`parse_str`].
+pub fn parse_file(path: impl AsRef<Path>) -> Result<Module, Diagnostics> {
+    parse_file_with(path, &CommandRegistry::default())
+}
+
+/// Same as [`parse_file`], allowing the custom commands in `registry`.
+pub fn parse_file_with(
+    path: impl AsRef<Path>,
+    registry: &CommandRegistry,
+) -> Result<Module, Diagnostics> {
+    let path = path.as_ref();
+    match std::fs::read_to_string(path) {
+        Ok(code) => parse_str_with(path.to_string_lossy(), &code, registry),
+        Err(io) => Err(Diagnostics {
+            name: path.to_string_lossy().into_owned(),
+            code: String::new(),
+            errors: ParseErrors::Io(io),
+        }),
+    }
+}
+
+/// Errors found while parsing a build script,
+/// along with the script itself so they can be shown in context.
+#[derive(Debug, Error)]
+#[error("{name}: {errors}")]
+pub struct Diagnostics {
+    /// Name used to refer to the build script
+    pub name: String,
+    /// Contents of the build script, empty if it couldn't be read
+    pub code: String,
+    pub errors: ParseErrors,
+}
+
+/// Errors from the first stage of parsing that failed.
+#[derive(Debug, Error)]
+pub enum ParseErrors {
+    #[error("Failed to read the build script: {0}")]
+    Io(io::Error),
+    #[error("Invalid syntax, {}", join_errors(.0))]
+    Lexer(Vec<SyntaxError<char>>),
+    #[error("Invalid syntax, {}", join_errors(.0))]
+    Parser(Vec<SyntaxError<Token>>),
+    #[error("{}", join_errors(.0))]
+    Lower(Vec<LowerError>),
+}
+
+fn join_errors(errors: &[impl Display]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// An error from the lexer, where `T` is [`char`], or from the parser, where `T` is [`Token`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError<T: Hash + Eq>(Simple<T>);
+
+/// What went wrong in a [`SyntaxError`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxErrorKind<'a, T> {
+    /// Something other than what was expected was found
+    Unexpected,
+    /// The `delimiter` opened at `span` is never closed
+    Unclosed {
+        span: Range<usize>,
+        delimiter: &'a T,
+    },
+    Custom(&'a str),
+}
+
+impl<T: Hash + Eq> SyntaxError<T> {
+    pub(crate) fn new(err: Simple<T>) -> Self {
+        Self(err)
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.0.span()
+    }
+
+    /// What was found instead, `None` at the end of the build script.
+    pub fn found(&self) -> Option<&T> {
+        self.0.found()
+    }
+
+    /// What could have been found instead, `None` for the end of the build script.
+    pub fn expected(&self) -> impl Iterator<Item = Option<&T>> {
+        self.0.expected().map(Option::as_ref)
+    }
+
+    /// What was being parsed, such as `unit definition`.
+    pub fn label(&self) -> Option<&'static str> {
+        self.0.label()
+    }
+
+    pub fn kind(&self) -> SyntaxErrorKind<'_, T> {
+        match self.0.reason() {
+            SimpleReason::Unexpected => SyntaxErrorKind::Unexpected,
+            SimpleReason::Unclosed { span, delimiter } => SyntaxErrorKind::Unclosed {
+                span: span.clone(),
+                delimiter,
+            },
+            SimpleReason::Custom(msg) => SyntaxErrorKind::Custom(msg),
+        }
+    }
+
+    /// Stable code identifying the kind of error, see `august explain`.
+    pub fn code(&self) -> &'static str {
+        match self.0.reason() {
+            SimpleReason::Unexpected => "A0001",
+            SimpleReason::Unclosed { .. } => "A0002",
+            SimpleReason::Custom(_) => "A0003",
+        }
+    }
+}
+
+impl<T: Hash + Eq + Display> Display for SyntaxError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: Hash + Eq + Display + std::fmt::Debug> std::error::Error for SyntaxError<T> {}
+
 #[derive(Debug, Clone, PartialEq, Eq, Error)]
 pub enum LowerError {
     #[error("Attempted to define another binding for pragma {0:?}")]
@@ -118,13 +436,127 @@ pub enum LowerError {
     DuplicateDependency(Spanned<String>, Spanned<String>),
     #[error("Meta item {1} defined multiple times in the same unit")]
     DuplicateMetaItem(Spanned<String>, Spanned<String>),
+    #[error("Meta item {0} has an invalid value {1:?}")]
+    InvalidMetaValue(Spanned<String>, String),
+    /// Includes the name of the closest existing unit if there is one
     #[error("Refers to a unit {0} that doesn't exist")]
-    NameError(Spanned<String>),
+    NameError(Spanned<String>, Option<String>),
+    /// Includes the closest registered command if there is one
+    #[error("Refers to a command {0}::{1} that doesn't exist")]
+    UnknownCommand(Spanned<String>, Spanned<String>, Option<String>),
+    /// The unit called with `do`, then the unit it leads to that needs the same lock
+    #[error("Calls {0} while holding lock {2}, which {1} also needs")]
+    LockDeadlock(Spanned<String>, Spanned<String>, String),
+    /// The unit called with `do`, then the unit it leads to that takes another lock,
+    /// along with the held lock and the lock that's taken
+    #[error("Calls {0} while holding lock {2}, so {1} takes lock {3}, which other units hold while taking lock {2}")]
+    LockOrder(Spanned<String>, Spanned<String>, String, String),
+}
+
+/// Every lock taken as part of calling `call`, following both `do` and `depends_on`,
+/// along with the first unit found that takes it.
+fn lock_takers<'a>(
+    units: &'a HashMap<Spanned<String>, Unit>,
+    call: &Spanned<String>,
+) -> HashMap<&'a str, &'a Spanned<String>> {
+    let mut takers = HashMap::default();
+    let mut seen = HashSet::default();
+    let mut stack = vec![call];
+
+    while let Some(name) = stack.pop() {
+        let Some((key, unit)) = units.get_key_value(name) else {
+            continue;
+        };
+        if !seen.insert(key) {
+            continue;
+        }
+        if let Some(lock) = unit.lock() {
+            takers.entry(lock).or_insert(key);
+        }
+        stack.extend(unit.deps());
+        stack.extend(unit.calls());
+    }
+
+    takers
+}
+
+/// Whether lock `to` can be taken while holding lock `from`, directly or through other locks,
+/// given pairs of held and taken locks from [`lock_takers`].
+fn lock_reaches(
+    lock_order: &[(&str, &Spanned<String>, &str, &Spanned<String>)],
+    from: &str,
+    to: &str,
+) -> bool {
+    let mut seen = HashSet::default();
+    let mut stack = vec![from];
+
+    while let Some(lock) = stack.pop() {
+        if lock == to {
+            return true;
+        }
+        if seen.insert(lock) {
+            stack.extend(
+                lock_order
+                    .iter()
+                    .filter(|(held, ..)| *held == lock)
+                    .map(|(_, _, taken, _)| *taken),
+            );
+        }
+    }
+
+    false
+}
+
+/// Name of the unit that a misspelt unit name most likely refers to.
+fn closest_unit(units: &HashMap<Spanned<String>, Unit>, name: &Spanned<String>) -> Option<String> {
+    suggest::closest(name.inner(), units.keys().map(|k| k.inner().as_str())).map(ToOwned::to_owned)
+}
+
+/// The registered or built-in command that a misspelt custom command most likely refers to.
+fn closest_command(
+    registry: &CommandRegistry,
+    module: &Spanned<String>,
+    name: &Spanned<String>,
+) -> Option<String> {
+    if let Some(cmd) = suggest::closest(name.inner(), registry.commands(module.inner())) {
+        return Some(format!("{module}::{cmd}"));
+    }
+    if let Some(m) = suggest::closest(module.inner(), registry.modules())
+        .filter(|m| registry.contains(m, name.inner()))
+    {
+        return Some(format!("{m}::{name}"));
+    }
+
+    // A typo of a built-in module, like `fss::copy`
+    let builtin = MODULES
+        .iter()
+        .filter(|(_, cmds)| cmds.contains(&name.inner().as_str()))
+        .map(|(m, _)| *m);
+    suggest::closest(module.inner(), builtin).map(|m| format!("{m}::{name}"))
+}
+
+impl LowerError {
+    /// Stable code identifying the kind of error, see `august explain`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::DuplicateExpose(..) => "A0101",
+            Self::DuplicateUnit(..) => "A0102",
+            Self::DuplicateDependency(..) => "A0103",
+            Self::DuplicateMetaItem(..) => "A0104",
+            Self::InvalidMetaValue(..) => "A0105",
+            Self::NameError(..) => "A0106",
+            Self::UnknownCommand(..) => "A0107",
+            Self::LockDeadlock(..) => "A0108",
+            Self::LockOrder(..) => "A0109",
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Unit {
     depends_on: HashSet<Spanned<String>>,
+    #[cfg_attr(feature = "serde", serde(with = "entries"))]
     pub meta: HashMap<Spanned<String>, String>,
     commands: Vec<Command>,
 }
@@ -168,6 +600,8 @@ impl Unit {
             for (var, val) in meta_items {
                 if let Some((other, _)) = meta.get_key_value(var) {
                     errors.push(LowerError::DuplicateMetaItem(other.clone(), var.clone()));
+                } else if var.inner() == "output" && val.parse::<OutputMode>().is_err() {
+                    errors.push(LowerError::InvalidMetaValue(var.clone(), val.clone()));
                 } else {
                     meta.insert(var.clone(), val.clone());
                 }
@@ -191,15 +625,97 @@ impl Unit {
     pub fn deps(&self) -> &HashSet<Spanned<String>> {
         &self.depends_on
     }
+
+    /// Human readable name of the unit, set with `@name` in the unit's `meta`.
+    pub fn display_name(&self) -> Option<&str> {
+        self.meta
+            .get(&Spanned::new("name".to_owned()))
+            .map(String::as_str)
+    }
+
+    /// How output from `exec` is written, set with `@output` in the unit's `meta`.
+    pub fn output(&self) -> Option<OutputMode> {
+        self.meta
+            .get(&Spanned::new("output".to_owned()))
+            .and_then(|o| o.parse().ok())
+    }
+
+    /// Commands run by the unit, excluding `meta` and `depends_on`.
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// Units called with `do`, including those inside `concurrent` blocks.
+    pub fn calls(&self) -> impl Iterator<Item = &Spanned<String>> {
+        fn walk<'a>(cmds: impl Iterator<Item = &'a Command>, out: &mut Vec<&'a Spanned<String>>) {
+            for cmd in cmds {
+                match cmd {
+                    Command::Do(units) => out.extend(units),
+                    Command::Concurrent(cmds) => walk(cmds.iter().map(Box::as_ref), out),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut calls = Vec::new();
+        walk(self.commands.iter(), &mut calls);
+        calls.into_iter()
+    }
+
+    /// Commands that lower back into this unit, with `meta` and `depends_on` first.
+    #[cfg(feature = "serde")]
+    fn into_commands(self) -> Vec<Command> {
+        let mut cmds = Vec::with_capacity(self.commands.len() + 2);
+        if !self.meta.is_empty() {
+            cmds.push(Command::Meta(self.meta.into_iter().collect()));
+        }
+        if !self.depends_on.is_empty() {
+            cmds.push(Command::DependsOn(self.depends_on.into_iter().collect()));
+        }
+        cmds.extend(self.commands);
+        cmds
+    }
+
+    /// Modules and names of custom commands, including those inside `concurrent` blocks.
+    pub fn custom_commands(&self) -> impl Iterator<Item = (&Spanned<String>, &Spanned<String>)> {
+        fn walk<'a>(
+            cmds: impl Iterator<Item = &'a Command>,
+            out: &mut Vec<(&'a Spanned<String>, &'a Spanned<String>)>,
+        ) {
+            for cmd in cmds {
+                match cmd {
+                    Command::Custom(module, name, _) => out.push((module, name)),
+                    Command::Concurrent(cmds) => walk(cmds.iter().map(Box::as_ref), out),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut customs = Vec::new();
+        walk(self.commands.iter(), &mut customs);
+        customs.into_iter()
+    }
+
+    /// Name of the lock set with `@lock` in the unit's `meta`.
+    ///
+    /// Units sharing a lock never run their commands at the same time.
+    pub fn lock(&self) -> Option<&str> {
+        self.meta
+            .get(&Spanned::new("lock".to_owned()))
+            .map(String::as_str)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Pragma {
     Test,
     Build,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Command {
     DependsOn(Vec<Spanned<String>>),
     Meta(Vec<(Spanned<String>, String)>),
@@ -210,9 +726,12 @@ pub enum Command {
     Fs(FsCommand),
     Io(IoCommand),
     Env(EnvCommand),
+    /// A command from a [`CommandRegistry`], called as `module::command("arg", ...)`
+    Custom(Spanned<String>, Spanned<String>, Vec<Spanned<String>>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FsCommand {
     Create(Spanned<String>),
     CreateDir(Spanned<String>),
@@ -232,6 +751,7 @@ pub enum FsCommand {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IoCommand {
     PrintLn(Spanned<String>),
     Print(Spanned<String>),
@@ -240,9 +760,369 @@ pub enum IoCommand {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EnvCommand {
     SetVar(Spanned<String>, Spanned<String>),
     RemoveVar(Spanned<String>),
     PathPush(Spanned<String>),
     PathRemove(Spanned<String>),
 }
+
+/// Displays commands as they would be written in a build script.
+impl Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Command::{Concurrent, Custom, DependsOn, Do, Env, Exec, Fs, Io, Meta};
+
+        match self {
+            DependsOn(units) => write!(f, "depends_on({})", join(units, ", ")),
+            Meta(items) => {
+                f.write_str("meta(")?;
+                for (i, (var, val)) in items.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "@{var} {}", Quoted(val))?;
+                }
+                f.write_str(")")
+            }
+            Do(units) => write!(f, "do({})", join(units, ", ")),
+            Exec(args) => {
+                f.write_str("exec(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(" ")?;
+                    }
+                    if is_bare_arg(arg.inner()) {
+                        f.write_str(arg.inner())?;
+                    } else {
+                        Quoted(arg.inner()).fmt(f)?;
+                    }
+                }
+                f.write_str(")")
+            }
+            Concurrent(cmds) => {
+                f.write_str("concurrent {")?;
+                for cmd in cmds {
+                    write!(f, " {cmd}")?;
+                }
+                f.write_str(" }")
+            }
+            Fs(cmd) => cmd.fmt(f),
+            Io(cmd) => cmd.fmt(f),
+            Env(cmd) => cmd.fmt(f),
+            Custom(module, name, args) => {
+                write!(f, "{module}::{name}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    Quoted(arg.inner()).fmt(f)?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+impl Display for FsCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use FsCommand::{
+            Copy, CopyTo, Create, CreateDir, EPrintFile, Move, MoveTo, PrintFile, Remove,
+        };
+
+        let binary_map = |map: &[(Spanned<String>, Option<Spanned<String>>)]| {
+            map.iter()
+                .map(|(src, dst)| match dst {
+                    Some(dst) => format!("{} => {}", Quoted(src.inner()), Quoted(dst.inner())),
+                    None => Quoted(src.inner()).to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match self {
+            Create(p) => write!(f, "fs::create({})", Quoted(p.inner())),
+            CreateDir(p) => write!(f, "fs::create_dir({})", Quoted(p.inner())),
+            Remove(p) => write!(f, "fs::remove({})", Quoted(p.inner())),
+            Move(src, dst) => write!(
+                f,
+                "fs::move({}, {})",
+                Quoted(src.inner()),
+                Quoted(dst.inner())
+            ),
+            MoveTo(dst, map) => write!(
+                f,
+                "fs::move_to({}, [{}])",
+                Quoted(dst.inner()),
+                binary_map(map)
+            ),
+            Copy(src, dst) => write!(
+                f,
+                "fs::copy({}, {})",
+                Quoted(src.inner()),
+                Quoted(dst.inner())
+            ),
+            CopyTo(dst, map) => write!(
+                f,
+                "fs::copy_to({}, [{}])",
+                Quoted(dst.inner()),
+                binary_map(map)
+            ),
+            PrintFile(p) => write!(f, "fs::print_file({})", Quoted(p.inner())),
+            EPrintFile(p) => write!(f, "fs::eprint_file({})", Quoted(p.inner())),
+        }
+    }
+}
+
+impl Display for IoCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use IoCommand::{EPrint, EPrintLn, Print, PrintLn};
+
+        match self {
+            PrintLn(t) => write!(f, "io::println({})", Quoted(t.inner())),
+            Print(t) => write!(f, "io::print({})", Quoted(t.inner())),
+            EPrintLn(t) => write!(f, "io::eprintln({})", Quoted(t.inner())),
+            EPrint(t) => write!(f, "io::eprint({})", Quoted(t.inner())),
+        }
+    }
+}
+
+impl Display for EnvCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use EnvCommand::{PathPush, PathRemove, RemoveVar, SetVar};
+
+        match self {
+            SetVar(var, val) => write!(
+                f,
+                "env::set_var({}, {})",
+                Quoted(var.inner()),
+                Quoted(val.inner())
+            ),
+            RemoveVar(var) => write!(f, "env::remove_var({})", Quoted(var.inner())),
+            PathPush(p) => write!(f, "env::path_push({})", Quoted(p.inner())),
+            PathRemove(p) => write!(f, "env::path_remove({})", Quoted(p.inner())),
+        }
+    }
+}
+
+fn join(items: &[Spanned<String>], sep: &str) -> String {
+    items
+        .iter()
+        .map(Spanned::inner)
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+/// Whether an `exec` argument can be written without quotes
+/// and still be read back as the same argument.
+fn is_bare_arg(arg: &str) -> bool {
+    let Some(first) = arg.chars().next() else {
+        return false;
+    };
+
+    if first.is_ascii_alphabetic() || first == '_' {
+        // Keywords are matched before identifiers, even as the start of a longer word
+        parser::KEYWORDS.iter().all(|kw| !arg.starts_with(kw))
+            && arg.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    } else {
+        !matches!(first, '"' | '@')
+            && !arg.starts_with("::")
+            && arg.chars().all(lexer::is_raw_ident_char)
+    }
+}
+
+/// Displays a string as an escaped string literal.
+pub(crate) struct Quoted<'a>(pub(crate) &'a str);
+
+impl Display for Quoted<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_char('"')?;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\x08' => f.write_str("\\b")?,
+                '\x0C' => f.write_str("\\f")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                c => f.write_char(c)?,
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_str, Command, Diagnostics, LowerError, Module, ParseErrors};
+
+    fn lower_errors(code: &str) -> Vec<LowerError> {
+        match parse_str("main.august", code) {
+            Err(Diagnostics {
+                errors: ParseErrors::Lower(errors),
+                ..
+            }) => errors,
+            res => panic!("expected lowering errors, found {res:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_call_needing_held_lock() {
+        let errors = lower_errors(
+            r#"
+            unit Migrate {
+                meta(@lock "database")
+                do(Seed)
+            }
+
+            unit Seed {
+                depends_on(Schema)
+            }
+
+            unit Schema {
+                meta(@lock "database")
+            }
+            "#,
+        );
+
+        assert_eq!(errors.len(), 1);
+        let LowerError::LockDeadlock(call, held, lock) = &errors[0] else {
+            panic!("expected a deadlock, found {:?}", errors[0]);
+        };
+        assert_eq!(
+            (call.inner().as_str(), held.inner().as_str()),
+            ("Seed", "Schema")
+        );
+        assert_eq!(lock, "database");
+    }
+
+    #[test]
+    fn rejects_locks_taken_in_opposite_orders() {
+        let errors = lower_errors(
+            r#"
+            unit Root {
+                depends_on(A, C)
+            }
+
+            unit A {
+                meta(@lock "L1")
+                exec(sleep 0.3)
+                do(B)
+            }
+
+            unit B {
+                meta(@lock "L2")
+            }
+
+            unit C {
+                meta(@lock "L2")
+                exec(sleep 0.3)
+                do(D)
+            }
+
+            unit D {
+                meta(@lock "L1")
+            }
+            "#,
+        );
+
+        let mut found = errors
+            .iter()
+            .map(|err| match err {
+                LowerError::LockOrder(call, taker, held, taken) => (
+                    call.inner().as_str(),
+                    taker.inner().as_str(),
+                    held.as_str(),
+                    taken.as_str(),
+                ),
+                err => panic!("expected a lock order error, found {err:?}"),
+            })
+            .collect::<Vec<_>>();
+        found.sort_unstable();
+        assert_eq!(found, [("B", "B", "L1", "L2"), ("D", "D", "L2", "L1")]);
+    }
+
+    #[test]
+    fn allows_locks_taken_in_the_same_order() {
+        let code = r#"
+            unit Root {
+                depends_on(A, C)
+            }
+
+            unit A {
+                meta(@lock "L1")
+                do(B)
+            }
+
+            unit B {
+                meta(@lock "L2")
+            }
+
+            unit C {
+                meta(@lock "L1")
+                do(B)
+            }
+        "#;
+
+        assert!(parse_str("main.august", code).is_ok());
+    }
+
+    #[test]
+    #[cfg(all(feature = "serde", feature = "diagnostics"))]
+    fn round_trips_through_serde() {
+        let code = r#"
+            expose Build as build
+
+            unit Build {
+                meta(@desc "Builds" @lock "target")
+                depends_on(Test)
+                concurrent {
+                    exec(cargo build)
+                    fs::copy("a", "b")
+                }
+                do(Test)
+            }
+
+            unit Test {
+                ~(cargo test)
+            }
+        "#;
+        let module = parse_str("main.august", code).unwrap();
+
+        let json = serde_json::to_string(&module).unwrap();
+        let de: Module = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(de.expose, module.expose);
+        assert_eq!(de.units, module.units);
+        // Spanned only compares values, Debug includes the spans too
+        assert_eq!(format!("{de:?}"), format!("{module:?}"));
+
+        let (name, unit) = de.units.get_index(0).unwrap();
+        assert_eq!(&code[name.span()], "Build");
+        let Command::Concurrent(cmds) = &unit.commands()[0] else {
+            panic!("expected concurrent, found {}", unit.commands()[0]);
+        };
+        let Command::Exec(args) = cmds[0].as_ref() else {
+            panic!("expected exec, found {}", cmds[0]);
+        };
+        assert_eq!(&code[args[1].span()], "build");
+    }
+
+    #[test]
+    #[cfg(all(feature = "serde", feature = "diagnostics"))]
+    fn checks_deserialized_modules() {
+        let module = parse_str(
+            "main.august",
+            "unit Build {\n    depends_on(Test)\n}\n\nunit Test {}\n",
+        )
+        .unwrap();
+        let json = serde_json::to_string(&module).unwrap();
+
+        // Only renames the dependency, so it refers to a unit that doesn't exist
+        let err = serde_json::from_str::<Module>(&json.replacen("\"Test\"", "\"Missing\"", 1))
+            .unwrap_err();
+        assert!(err.to_string().contains("Missing"), "{err}");
+    }
+}
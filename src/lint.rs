@@ -0,0 +1,503 @@
+//! Warnings about build scripts that are valid but likely to be mistakes.
+
+use std::{
+    ops::Range,
+    path::{Component, Path},
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+use crate::{
+    parser::Spanned, suggest, Command, EnvCommand, FsCommand, HashMap, HashSet, IoCommand, Module,
+};
+
+/// Meta items that August reads or documents.
+pub const KNOWN_META: &[&str] = &[
+    "name",
+    "desc",
+    "description",
+    "deps",
+    "calls",
+    "output",
+    "lock",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A unit that isn't exposed and isn't run by any exposed unit.
+    UnreachableUnit,
+    /// A unit that is both a dependency and called with `do` by the same unit.
+    DependsOnAndDo,
+    /// A unit without any commands or dependencies.
+    EmptyUnit,
+    /// A `concurrent` block with only one command.
+    SingleConcurrent,
+    /// `fs::remove` of a path outside the directory of the build script.
+    RemoveOutsideScript,
+    /// A meta item that August doesn't know about.
+    UnknownMeta,
+}
+
+impl Lint {
+    pub const ALL: &'static [Self] = &[
+        Self::UnreachableUnit,
+        Self::DependsOnAndDo,
+        Self::EmptyUnit,
+        Self::SingleConcurrent,
+        Self::RemoveOutsideScript,
+        Self::UnknownMeta,
+    ];
+
+    /// Name used to configure the lint.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::UnreachableUnit => "unreachable-unit",
+            Self::DependsOnAndDo => "depends-on-and-do",
+            Self::EmptyUnit => "empty-unit",
+            Self::SingleConcurrent => "single-concurrent",
+            Self::RemoveOutsideScript => "remove-outside-script",
+            Self::UnknownMeta => "unknown-meta",
+        }
+    }
+
+    /// Stable code identifying the lint, see `august explain`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnreachableUnit => "A0401",
+            Self::DependsOnAndDo => "A0402",
+            Self::EmptyUnit => "A0403",
+            Self::SingleConcurrent => "A0404",
+            Self::RemoveOutsideScript => "A0405",
+            Self::UnknownMeta => "A0406",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Unknown lint {0}")]
+pub struct UnknownLint(pub String);
+
+impl FromStr for Lint {
+    type Err = UnknownLint;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|l| l.name() == s)
+            .copied()
+            .ok_or_else(|| UnknownLint(s.to_owned()))
+    }
+}
+
+/// How a lint is reported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// Not reported.
+    Allow,
+    /// Reported as a warning.
+    #[default]
+    Warn,
+    /// Reported as an error.
+    Deny,
+}
+
+/// Levels of each lint, every lint warns unless set otherwise.
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<Lint, Level>,
+    deny_warnings: bool,
+}
+
+impl LintConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn level(mut self, lint: Lint, level: Level) -> Self {
+        self.levels.insert(lint, level);
+        self
+    }
+
+    /// Treats every lint that would warn as denied.
+    #[inline]
+    pub fn deny_warnings(mut self) -> Self {
+        self.deny_warnings = true;
+        self
+    }
+
+    pub fn get(&self, lint: Lint) -> Level {
+        match self.levels.get(&lint).copied().unwrap_or_default() {
+            Level::Warn if self.deny_warnings => Level::Deny,
+            level => level,
+        }
+    }
+}
+
+/// A lint that was found in a build script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub lint: Lint,
+    /// Never [`Level::Allow`]
+    pub level: Level,
+    pub message: String,
+    pub span: Range<usize>,
+    /// Other relevant parts of the build script
+    pub labels: Vec<(Range<usize>, String)>,
+    pub help: Option<String>,
+}
+
+impl Warning {
+    fn new(lint: Lint, span: Range<usize>, message: impl Into<String>) -> Self {
+        Self {
+            lint,
+            level: Level::Warn,
+            message: message.into(),
+            span,
+            labels: Vec::new(),
+            help: None,
+        }
+    }
+
+    fn with_label(mut self, span: Range<usize>, message: impl Into<String>) -> Self {
+        self.labels.push((span, message.into()));
+        self
+    }
+
+    fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+}
+
+/// Checks a module for every lint that isn't allowed in `config`.
+pub fn lint(module: &Module, config: &LintConfig) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let reachable = reachable(module);
+
+    for (name, unit) in module.units() {
+        if !reachable.contains(name) {
+            warnings.push(
+                Warning::new(
+                    Lint::UnreachableUnit,
+                    name.span(),
+                    format!("Unit {name} is never run by an exposed unit"),
+                )
+                .with_help(format!(
+                    "Expose {name} or remove it if it isn't run with `august run`."
+                )),
+            );
+        }
+
+        for call in unit.calls() {
+            if let Some(dep) = unit.deps().get(call) {
+                warnings.push(
+                    Warning::new(
+                        Lint::DependsOnAndDo,
+                        call.span(),
+                        format!(
+                            "Unit {call} is called with do after already running as a dependency"
+                        ),
+                    )
+                    .with_label(dep.span(), "Already a dependency here")
+                    .with_help(
+                        "This unit runs once as a dependency and again through do; remove one of them if that isn't intended.",
+                    ),
+                );
+            }
+        }
+
+        if unit.commands().is_empty() && unit.deps().is_empty() {
+            warnings.push(
+                Warning::new(
+                    Lint::EmptyUnit,
+                    name.span(),
+                    format!("Unit {name} doesn't do anything"),
+                )
+                .with_help("Add commands or dependencies to the unit, or remove it."),
+            );
+        }
+
+        walk(unit.commands(), &mut |cmd| match cmd {
+            Command::Concurrent(cmds) if cmds.len() == 1 => warnings.push(
+                Warning::new(
+                    Lint::SingleConcurrent,
+                    span(&cmds[0]).unwrap_or_else(|| name.span()),
+                    "Concurrent block only contains one command",
+                )
+                .with_help("Move the command out of the concurrent block."),
+            ),
+            Command::Fs(FsCommand::Remove(path)) if leaves_directory(path.inner()) => warnings
+                .push(
+                    Warning::new(
+                        Lint::RemoveOutsideScript,
+                        path.span(),
+                        format!(
+                            "Removes {path} which is outside the directory of the build script"
+                        ),
+                    )
+                    .with_help("Paths are relative to the build script, check this is intended."),
+                ),
+            _ => {}
+        });
+
+        for key in unit.meta.keys() {
+            if KNOWN_META.contains(&key.inner().as_str()) {
+                continue;
+            }
+            warnings.push(
+                Warning::new(
+                    Lint::UnknownMeta,
+                    key.span(),
+                    format!("Meta item {key} isn't used by August"),
+                )
+                .with_help(
+                    match suggest::closest(key.inner(), KNOWN_META.iter().copied()) {
+                        Some(s) => format!("Did you mean {s}?"),
+                        None => format!(
+                            "Known meta items are {}, allow this lint if it's intended.",
+                            KNOWN_META.join(", ")
+                        ),
+                    },
+                ),
+            );
+        }
+    }
+
+    warnings
+        .into_iter()
+        .filter_map(|mut w| {
+            w.level = config.get(w.lint);
+            (w.level != Level::Allow).then_some(w)
+        })
+        .collect()
+}
+
+/// Units run by an exposed unit, through dependencies or `do`, including the exposed units.
+fn reachable(module: &Module) -> HashSet<&Spanned<String>> {
+    let mut seen = HashSet::default();
+    let mut stack: Vec<_> = module.expose.values().collect();
+
+    while let Some(name) = stack.pop() {
+        let Some((key, unit)) = module.units().get_key_value(name) else {
+            continue;
+        };
+        if seen.insert(key) {
+            stack.extend(unit.deps());
+            stack.extend(unit.calls());
+        }
+    }
+
+    seen
+}
+
+/// Visits every command in a unit, including those in `concurrent` blocks.
+fn walk<'a>(cmds: &'a [Command], f: &mut impl FnMut(&'a Command)) {
+    for cmd in cmds {
+        f(cmd);
+        if let Command::Concurrent(inner) = cmd {
+            for c in inner {
+                walk(std::slice::from_ref(c.as_ref()), f);
+            }
+        }
+    }
+}
+
+/// Span covering every argument of a command.
+fn span(cmd: &Command) -> Option<Range<usize>> {
+    fn spans(cmd: &Command, out: &mut Vec<Range<usize>>) {
+        let push = |s: &Spanned<String>, out: &mut Vec<Range<usize>>| out.push(s.span());
+        match cmd {
+            Command::DependsOn(args) | Command::Do(args) | Command::Exec(args) => {
+                args.iter().for_each(|s| push(s, out));
+            }
+            Command::Custom(module, name, args) => {
+                push(module, out);
+                push(name, out);
+                args.iter().for_each(|s| push(s, out));
+            }
+            Command::Meta(items) => items.iter().for_each(|(k, _)| push(k, out)),
+            Command::Concurrent(cmds) => cmds.iter().for_each(|c| spans(c, out)),
+            Command::Fs(
+                FsCommand::Create(p)
+                | FsCommand::CreateDir(p)
+                | FsCommand::Remove(p)
+                | FsCommand::PrintFile(p)
+                | FsCommand::EPrintFile(p),
+            )
+            | Command::Io(
+                IoCommand::PrintLn(p)
+                | IoCommand::Print(p)
+                | IoCommand::EPrintLn(p)
+                | IoCommand::EPrint(p),
+            )
+            | Command::Env(
+                EnvCommand::RemoveVar(p) | EnvCommand::PathPush(p) | EnvCommand::PathRemove(p),
+            ) => push(p, out),
+            Command::Fs(FsCommand::Move(a, b) | FsCommand::Copy(a, b))
+            | Command::Env(EnvCommand::SetVar(a, b)) => {
+                push(a, out);
+                push(b, out);
+            }
+            Command::Fs(FsCommand::MoveTo(dst, srcs) | FsCommand::CopyTo(dst, srcs)) => {
+                push(dst, out);
+                for (src, rename) in srcs {
+                    push(src, out);
+                    if let Some(r) = rename {
+                        push(r, out);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    spans(cmd, &mut out);
+    let start = out.iter().map(|s| s.start).min()?;
+    let end = out.iter().map(|s| s.end).max()?;
+    Some(start..end)
+}
+
+/// Whether a path relative to the build script points outside of its directory.
+fn leaves_directory(path: &str) -> bool {
+    let mut depth = 0usize;
+    for component in Path::new(path).components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => return true,
+            Component::CurDir => {}
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(d) => depth = d,
+                None => return true,
+            },
+            Component::Normal(_) => depth += 1,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint, Level, Lint, LintConfig};
+    use crate::parse_str;
+
+    /// Lints `code` and returns the lint, level and source text of each warning.
+    fn warnings(code: &str, config: &LintConfig) -> Vec<(Lint, Level, String)> {
+        let module = parse_str("main.august", code).unwrap();
+        let mut warnings: Vec<_> = lint(&module, config)
+            .into_iter()
+            .map(|w| (w.lint, w.level, code[w.span].to_owned()))
+            .collect();
+        warnings.sort_by(|a, b| a.2.cmp(&b.2));
+        warnings
+    }
+
+    fn lints(code: &str) -> Vec<(Lint, String)> {
+        warnings(code, &LintConfig::new())
+            .into_iter()
+            .map(|(lint, _, text)| (lint, text))
+            .collect()
+    }
+
+    #[test]
+    fn allows_units_run_by_exposed_units() {
+        let code = "expose Build as build\n\nunit Build {\n    depends_on(Test)\n    exec(cargo build)\n}\n\nunit Test {\n    do(Lints)\n    exec(cargo test)\n}\n\nunit Lints {\n    exec(cargo clippy)\n}\n";
+        assert_eq!(lints(code), []);
+    }
+
+    #[test]
+    fn reports_unreachable_units() {
+        let code = "expose Build as build\n\nunit Build {\n    exec(cargo build)\n}\n\nunit Old {\n    exec(make)\n}\n";
+        assert_eq!(lints(code), [(Lint::UnreachableUnit, "Old".to_owned())]);
+    }
+
+    #[test]
+    fn reports_units_only_used_by_unreachable_units() {
+        let code = "expose Build as build\n\nunit Build {\n    exec(cargo build)\n}\n\nunit A {\n    depends_on(B)\n    exec(a)\n}\n\nunit B {\n    do(A)\n    exec(b)\n}\n";
+        assert_eq!(
+            lints(code),
+            [
+                (Lint::UnreachableUnit, "A".to_owned()),
+                (Lint::UnreachableUnit, "B".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_dependencies_also_called_with_do() {
+        let code = "expose Build as build\n\nunit Build {\n    depends_on(Test)\n    do(Test)\n}\n\nunit Test {\n    exec(cargo test)\n}\n";
+        assert_eq!(lints(code), [(Lint::DependsOnAndDo, "Test".to_owned())]);
+    }
+
+    #[test]
+    fn reports_empty_units() {
+        let code = "expose Build as build\n\nunit Build {\n    meta(@desc \"Nothing\")\n}\n";
+        assert_eq!(lints(code), [(Lint::EmptyUnit, "Build".to_owned())]);
+    }
+
+    #[test]
+    fn reports_concurrent_blocks_with_one_command() {
+        let code = "expose Build as build\n\nunit Build {\n    concurrent {\n        exec(cargo build)\n    }\n    concurrent {\n        exec(a)\n        exec(b)\n    }\n}\n";
+        assert_eq!(
+            lints(code),
+            [(Lint::SingleConcurrent, "cargo build".to_owned())]
+        );
+    }
+
+    #[test]
+    fn reports_removing_paths_outside_the_script() {
+        let code = "expose Clean as build\n\nunit Clean {\n    fs::remove(\"target\")\n    fs::remove(\"a/../b\")\n    fs::remove(\"../dist\")\n    fs::remove(\"/tmp\")\n}\n";
+        assert_eq!(
+            lints(code),
+            [
+                (Lint::RemoveOutsideScript, "\"../dist\"".to_owned()),
+                (Lint::RemoveOutsideScript, "\"/tmp\"".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unknown_meta_items() {
+        let code = "expose Build as build\n\nunit Build {\n    meta(\n        @desc \"Builds\"\n        @ouptut \"grouped\"\n    )\n    exec(cargo build)\n}\n";
+        let module = parse_str("main.august", code).unwrap();
+        let warnings = lint(&module, &LintConfig::new());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].lint, Lint::UnknownMeta);
+        assert_eq!(&code[warnings[0].span.clone()], "ouptut");
+        assert_eq!(warnings[0].help.as_deref(), Some("Did you mean output?"));
+    }
+
+    #[test]
+    fn applies_lint_levels() {
+        let code =
+            "expose Build as build\n\nunit Build {\n    exec(cargo build)\n}\n\nunit Old {}\n";
+        let config = LintConfig::new()
+            .level(Lint::UnreachableUnit, Level::Allow)
+            .level(Lint::EmptyUnit, Level::Deny);
+        assert_eq!(
+            warnings(code, &config),
+            [(Lint::EmptyUnit, Level::Deny, "Old".to_owned())]
+        );
+    }
+
+    #[test]
+    fn denies_warnings_but_not_allowed_lints() {
+        let code =
+            "expose Build as build\n\nunit Build {\n    exec(cargo build)\n}\n\nunit Old {}\n";
+        let config = LintConfig::new()
+            .level(Lint::EmptyUnit, Level::Allow)
+            .deny_warnings();
+        assert_eq!(
+            warnings(code, &config),
+            [(Lint::UnreachableUnit, Level::Deny, "Old".to_owned())]
+        );
+    }
+
+    #[test]
+    fn parses_lint_names() {
+        for lint in Lint::ALL {
+            assert_eq!(lint.name().parse(), Ok(*lint));
+        }
+        assert!("unreachable".parse::<Lint>().is_err());
+    }
+}
@@ -0,0 +1,801 @@
+//! Language server for editors, started with `august lsp`.
+//!
+//! Build scripts are re-parsed on every change,
+//! and unit names are looked up in the [`AST`] once the build script parses,
+//! or in the tokens of its [`Cst`] while it doesn't.
+
+use std::{collections::HashMap, error::Error, ops::Range};
+
+use august_build::{
+    cst::Cst,
+    diagnostics::{Diagnostic, LintFormatter, LowerErrorFormatter, Severity},
+    lexer::{Delim, Token},
+    lint::{self, LintConfig},
+    parse_ast,
+    parser::{Spanned, AST, CORE_COMMANDS, KEYWORDS, MODULES},
+    Command, Module,
+};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as NotificationTrait, PublishDiagnostics,
+    },
+    request::{Completion, GotoDefinition, HoverRequest, References, Rename},
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    DiagnosticRelatedInformation, DiagnosticSeverity, GotoDefinitionParams, GotoDefinitionResponse,
+    Hover, HoverContents, HoverParams, HoverProviderCapability, Location, MarkupContent,
+    MarkupKind, NumberOrString, OneOf, Position, PublishDiagnosticsParams, ReferenceParams,
+    RenameParams, ServerCapabilities, TextDocumentPositionParams, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextEdit, Uri, WorkspaceEdit,
+};
+
+pub type LspError = Box<dyn Error + Send + Sync>;
+
+/// Runs the language server until the client asks it to exit.
+///
+/// Use [`Connection::stdio`] for editors, or [`Connection::memory`] to drive it from a client in the same process.
+pub fn run(connection: Connection) -> Result<(), LspError> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![":".to_owned()]),
+            ..Default::default()
+        }),
+        rename_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut server = Server {
+        connection,
+        documents: HashMap::new(),
+    };
+    server.main_loop()
+}
+
+struct Server {
+    connection: Connection,
+    documents: HashMap<Uri, Document>,
+}
+
+impl Server {
+    fn main_loop(&mut self) -> Result<(), LspError> {
+        while let Ok(msg) = self.connection.receiver.recv() {
+            match msg {
+                Message::Request(req) => {
+                    if self.connection.handle_shutdown(&req)? {
+                        return Ok(());
+                    }
+                    let resp = self.request(req);
+                    self.connection.sender.send(resp.into())?;
+                }
+                Message::Notification(n) => self.notification(n)?,
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn request(&self, req: Request) -> Response {
+        use lsp_types::request::Request as _;
+
+        match req.method.as_str() {
+            GotoDefinition::METHOD => handle::<GotoDefinition>(req, |p| Ok(self.definition(p))),
+            References::METHOD => handle::<References>(req, |p| Ok(self.references(p))),
+            HoverRequest::METHOD => handle::<HoverRequest>(req, |p| Ok(self.hover(p))),
+            Completion::METHOD => handle::<Completion>(req, |p| Ok(self.completion(p))),
+            Rename::METHOD => handle::<Rename>(req, |p| self.rename(p)),
+            _ => Response::new_err(
+                req.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request {}", req.method),
+            ),
+        }
+    }
+
+    fn notification(&mut self, n: Notification) -> Result<(), LspError> {
+        match n.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = n.extract::<<DidOpenTextDocument as NotificationTrait>::Params>(
+                    DidOpenTextDocument::METHOD,
+                )?;
+                let doc = params.text_document;
+                self.update(doc.uri, doc.text, Some(doc.version))
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = n.extract::<<DidChangeTextDocument as NotificationTrait>::Params>(
+                    DidChangeTextDocument::METHOD,
+                )?;
+                // Only full changes are asked for
+                match params.content_changes.into_iter().last() {
+                    Some(change) => self.update(
+                        params.text_document.uri,
+                        change.text,
+                        Some(params.text_document.version),
+                    ),
+                    None => Ok(()),
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = n.extract::<<DidCloseTextDocument as NotificationTrait>::Params>(
+                    DidCloseTextDocument::METHOD,
+                )?;
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                self.publish(uri, Vec::new(), None)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Re-parses a document and sends its diagnostics.
+    fn update(&mut self, uri: Uri, text: String, version: Option<i32>) -> Result<(), LspError> {
+        let doc = Document::new(&uri, text);
+        let diagnostics = doc.diagnostics.clone();
+        self.documents.insert(uri.clone(), doc);
+        self.publish(uri, diagnostics, version)
+    }
+
+    fn publish(
+        &self,
+        uri: Uri,
+        diagnostics: Vec<lsp_types::Diagnostic>,
+        version: Option<i32>,
+    ) -> Result<(), LspError> {
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version,
+        };
+        self.connection
+            .sender
+            .send(Notification::new(PublishDiagnostics::METHOD.to_owned(), params).into())?;
+        Ok(())
+    }
+
+    /// The document and unit name at a position.
+    fn symbol_at(&self, pos: &TextDocumentPositionParams) -> Option<(&Document, &Symbol)> {
+        let doc = self.documents.get(&pos.text_document.uri)?;
+        let offset = doc.offset(pos.position);
+        let symbol = doc
+            .symbols
+            .iter()
+            .find(|s| s.span.start <= offset && offset <= s.span.end)?;
+        Some((doc, symbol))
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let pos = params.text_document_position_params;
+        let (doc, symbol) = self.symbol_at(&pos)?;
+        let def = doc
+            .symbols
+            .iter()
+            .find(|s| s.definition && s.name == symbol.name)?;
+        Some(GotoDefinitionResponse::Scalar(Location::new(
+            pos.text_document.uri,
+            doc.range(&def.span),
+        )))
+    }
+
+    fn references(&self, params: ReferenceParams) -> Option<Vec<Location>> {
+        let pos = params.text_document_position;
+        let (doc, symbol) = self.symbol_at(&pos)?;
+        Some(
+            doc.symbols
+                .iter()
+                .filter(|s| s.name == symbol.name)
+                .filter(|s| params.context.include_declaration || !s.definition)
+                .map(|s| Location::new(pos.text_document.uri.clone(), doc.range(&s.span)))
+                .collect(),
+        )
+    }
+
+    /// Shows the `meta` of a unit.
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let (doc, symbol) = self.symbol_at(&params.text_document_position_params)?;
+        let cmds = doc.ast.as_ref()?.iter().find_map(|a| match a {
+            AST::Unit(name, cmds) if *name.inner() == symbol.name => Some(cmds),
+            _ => None,
+        })?;
+
+        let mut value = format!("```august\nunit {}\n```", symbol.name);
+        for cmd in cmds {
+            if let Command::Meta(items) = cmd {
+                for (var, val) in items {
+                    value.push_str(&format!("\n\n**@{var}** {val}"));
+                }
+            }
+        }
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(doc.range(&symbol.span)),
+        })
+    }
+
+    /// Completes the commands of a module after `fs::`,
+    /// or commands, modules and unit names otherwise.
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let pos = params.text_document_position;
+        let doc = self.documents.get(&pos.text_document.uri)?;
+        let offset = doc.offset(pos.position);
+
+        let before = doc.chars[..offset]
+            .iter()
+            .rev()
+            .skip_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+            .collect::<String>()
+            .chars()
+            .rev()
+            .collect::<String>();
+        let module = before.strip_suffix("::").map(|prefix| {
+            prefix
+                .rsplit(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .next()
+                .unwrap_or_default()
+        });
+
+        let item = |label: &str, kind, detail: &str| CompletionItem {
+            label: label.to_owned(),
+            kind: Some(kind),
+            detail: Some(detail.to_owned()),
+            ..Default::default()
+        };
+
+        let items = match module {
+            Some(module) => {
+                let (name, commands) = MODULES
+                    .iter()
+                    .find(|(m, _)| m.eq_ignore_ascii_case(module))?;
+                commands
+                    .iter()
+                    .map(|c| item(c, CompletionItemKind::FUNCTION, &format!("{name}::{c}")))
+                    .collect()
+            }
+            None => CORE_COMMANDS
+                .iter()
+                .map(|c| item(c, CompletionItemKind::KEYWORD, "command"))
+                .chain(
+                    MODULES
+                        .iter()
+                        .map(|(m, _)| item(m, CompletionItemKind::MODULE, "module")),
+                )
+                .chain(
+                    doc.units
+                        .iter()
+                        .map(|u| item(u, CompletionItemKind::CLASS, "unit")),
+                )
+                .collect(),
+        };
+
+        Some(CompletionResponse::Array(items))
+    }
+
+    /// Renames a unit where it's defined and everywhere it's used.
+    fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>, String> {
+        let pos = params.text_document_position;
+        let Some((doc, symbol)) = self.symbol_at(&pos) else {
+            return Ok(None);
+        };
+
+        let name = params.new_name;
+        let valid = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            // Keywords are matched before identifiers, even as the start of a longer word
+            && KEYWORDS.iter().all(|kw| !name.starts_with(kw));
+        if !valid {
+            return Err(format!("{name} isn't a valid unit name"));
+        }
+        if name != symbol.name && doc.units.contains(&name) {
+            return Err(format!("A unit named {name} already exists"));
+        }
+
+        let edits = doc
+            .symbols
+            .iter()
+            .filter(|s| s.name == symbol.name)
+            .map(|s| TextEdit::new(doc.range(&s.span), name.clone()))
+            .collect();
+
+        Ok(Some(WorkspaceEdit::new(
+            [(pos.text_document.uri, edits)].into_iter().collect(),
+        )))
+    }
+}
+
+/// Runs a request handler, responding with an error if the request is invalid.
+fn handle<R>(req: Request, f: impl FnOnce(R::Params) -> Result<R::Result, String>) -> Response
+where
+    R: lsp_types::request::Request,
+{
+    let id = req.id.clone();
+    match req.extract::<R::Params>(R::METHOD) {
+        Ok((id, params)) => match f(params) {
+            Ok(result) => Response::new_ok(id, result),
+            Err(message) => Response::new_err(id, ErrorCode::InvalidParams as i32, message),
+        },
+        Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+    }
+}
+
+/// A unit name in the build script, either where the unit is defined or where it's used.
+struct Symbol {
+    name: String,
+    span: Range<usize>,
+    definition: bool,
+}
+
+fn symbols(ast: &[AST]) -> Vec<Symbol> {
+    fn symbol(name: &Spanned<String>, definition: bool) -> Symbol {
+        Symbol {
+            name: name.inner_owned(),
+            span: name.span(),
+            definition,
+        }
+    }
+
+    fn walk(cmds: &[Command], out: &mut Vec<Symbol>) {
+        for cmd in cmds {
+            match cmd {
+                Command::DependsOn(units) | Command::Do(units) => {
+                    out.extend(units.iter().map(|u| symbol(u, false)));
+                }
+                Command::Concurrent(cmds) => {
+                    for c in cmds {
+                        walk(std::slice::from_ref(c.as_ref()), out);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    for item in ast {
+        match item {
+            AST::Expose(_, unit) => out.push(symbol(unit, false)),
+            AST::Unit(name, cmds) => {
+                out.push(symbol(name, true));
+                walk(cmds, &mut out);
+            }
+            AST::Err => {}
+        }
+    }
+    out
+}
+
+/// Unit names found in the tokens of a build script that doesn't parse.
+fn cst_symbols(cst: &Cst) -> Vec<Symbol> {
+    let tokens = cst.tokens();
+    let symbol = |i: usize, definition| match tokens.get(i) {
+        Some((Token::Ident(name), span)) => Some(Symbol {
+            name: name.clone(),
+            span: span.clone(),
+            definition,
+        }),
+        _ => None,
+    };
+
+    let mut out = Vec::new();
+    for (i, (token, _)) in tokens.iter().enumerate() {
+        match token {
+            Token::Unit => out.extend(symbol(i + 1, true)),
+            Token::Expose => out.extend(symbol(i + 1, false)),
+            Token::Ident(cmd)
+                if (cmd == "depends_on" || cmd == "do")
+                    && (i == 0 || tokens[i - 1].0 != Token::DoubleColon)
+                    && tokens.get(i + 1).map(|(t, _)| t)
+                        == Some(&Token::OpenDelim(Delim::Round)) =>
+            {
+                for (j, (token, _)) in tokens.iter().enumerate().skip(i + 2) {
+                    match token {
+                        Token::Ident(_) => out.extend(symbol(j, false)),
+                        Token::Comma => {}
+                        _ => break,
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// An open build script.
+struct Document {
+    chars: Vec<char>,
+    /// Offset in chars of the start of each line
+    line_starts: Vec<usize>,
+    /// Missing if the build script doesn't parse
+    ast: Option<Vec<AST>>,
+    /// Taken from the tokens if the build script doesn't parse
+    symbols: Vec<Symbol>,
+    /// Names of the units defined in the build script
+    units: Vec<String>,
+    diagnostics: Vec<lsp_types::Diagnostic>,
+}
+
+impl Document {
+    fn new(uri: &Uri, text: String) -> Self {
+        let chars = text.chars().collect::<Vec<_>>();
+        let line_starts = std::iter::once(0)
+            .chain(
+                chars
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| **c == '\n')
+                    .map(|(i, _)| i + 1),
+            )
+            .collect();
+
+        let (ast, diagnostics) = analyse(uri.as_str(), &text);
+        let symbols = match &ast {
+            Some(ast) => symbols(ast),
+            None => cst_symbols(&Cst::parse(&text)),
+        };
+        let units = symbols
+            .iter()
+            .filter(|s| s.definition)
+            .map(|s| s.name.clone())
+            .collect();
+
+        let mut doc = Self {
+            chars,
+            line_starts,
+            ast,
+            symbols,
+            units,
+            diagnostics: Vec::new(),
+        };
+        doc.diagnostics = diagnostics
+            .into_iter()
+            .map(|d| doc.diagnostic(uri, d))
+            .collect();
+        doc
+    }
+
+    /// Converts an offset in chars to a line and UTF-16 column.
+    fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.chars.len());
+        let line = self.line_starts.partition_point(|s| *s <= offset) - 1;
+        let character = self.chars[self.line_starts[line]..offset]
+            .iter()
+            .map(|c| c.len_utf16())
+            .sum::<usize>();
+        Position::new(line as u32, character as u32)
+    }
+
+    /// Converts a line and UTF-16 column to an offset in chars.
+    fn offset(&self, pos: Position) -> usize {
+        let Some(mut offset) = self.line_starts.get(pos.line as usize).copied() else {
+            return self.chars.len();
+        };
+        let mut column = 0;
+        while offset < self.chars.len()
+            && self.chars[offset] != '\n'
+            && column < pos.character as usize
+        {
+            column += self.chars[offset].len_utf16();
+            offset += 1;
+        }
+        offset
+    }
+
+    fn range(&self, span: &Range<usize>) -> lsp_types::Range {
+        lsp_types::Range::new(self.position(span.start), self.position(span.end))
+    }
+
+    fn diagnostic(&self, uri: &Uri, d: Diagnostic) -> lsp_types::Diagnostic {
+        let related = d
+            .labels
+            .iter()
+            .map(|l| DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), self.range(&l.span)),
+                message: l.message.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        lsp_types::Diagnostic {
+            range: self.range(&d.span),
+            severity: Some(match d.severity {
+                Severity::Error => DiagnosticSeverity::ERROR,
+                Severity::Warning => DiagnosticSeverity::WARNING,
+            }),
+            code: d.code.map(|c| NumberOrString::String(c.to_owned())),
+            source: Some("august".to_owned()),
+            message: match d.help {
+                Some(help) => format!("{}\n{help}", d.message),
+                None => d.message,
+            },
+            related_information: (!related.is_empty()).then_some(related),
+            ..Default::default()
+        }
+    }
+}
+
+/// Parses, lowers and lints a build script,
+/// stopping at the first stage with errors.
+fn analyse(file_name: &str, text: &str) -> (Option<Vec<AST>>, Vec<Diagnostic>) {
+    let ast = match parse_ast(file_name, text) {
+        Ok(ast) => ast,
+        Err(d) => return (None, d.diagnostics()),
+    };
+
+    let diagnostics = match Module::lower(ast.clone()) {
+        Ok(module) => LintFormatter::new(lint::lint(&module, &LintConfig::new()), file_name, text)
+            .diagnostics(),
+        Err(err) => LowerErrorFormatter::new(err, file_name, text).diagnostics(),
+    };
+    (Some(ast), diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::{self, JoinHandle};
+
+    use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+    use lsp_types::{
+        notification::{
+            DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized,
+            Notification as NotificationTrait, PublishDiagnostics,
+        },
+        request::{
+            Completion, GotoDefinition, Initialize, Rename, Request as RequestTrait, Shutdown,
+        },
+        CompletionParams, CompletionResponse, DidChangeTextDocumentParams,
+        DidOpenTextDocumentParams, GotoDefinitionParams, GotoDefinitionResponse, InitializeParams,
+        InitializeResult, InitializedParams, OneOf, Position, PublishDiagnosticsParams, Range,
+        RenameParams, TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
+        TextDocumentPositionParams, Uri, VersionedTextDocumentIdentifier,
+    };
+
+    use super::{run, LspError};
+
+    const CODE: &str = "expose Build as build\n\nunit Build {\n    depends_on(Test)\n}\n\nunit Test {\n    exec(cargo test)\n}\n";
+
+    /// An editor talking to a server running on another thread.
+    struct Client {
+        connection: Connection,
+        server: Option<JoinHandle<Result<(), LspError>>>,
+        next_id: i32,
+        initialize: InitializeResult,
+    }
+
+    impl Client {
+        fn start() -> Self {
+            let (server, connection) = Connection::memory();
+            let mut client = Self {
+                connection,
+                server: Some(thread::spawn(|| run(server))),
+                next_id: 0,
+                initialize: InitializeResult::default(),
+            };
+            client.initialize = client
+                .request::<Initialize>(InitializeParams::default())
+                .unwrap();
+            client.notify::<Initialized>(InitializedParams {});
+            client
+        }
+
+        fn request<R: RequestTrait>(&mut self, params: R::Params) -> Result<R::Result, String> {
+            self.next_id += 1;
+            let id = RequestId::from(self.next_id);
+            self.connection
+                .sender
+                .send(Request::new(id.clone(), R::METHOD.to_owned(), params).into())
+                .unwrap();
+
+            loop {
+                match self.connection.receiver.recv().unwrap() {
+                    Message::Response(Response {
+                        id: r,
+                        result,
+                        error,
+                    }) if r == id => {
+                        return match error {
+                            Some(e) => Err(e.message),
+                            None => Ok(serde_json::from_value(result.unwrap_or_default()).unwrap()),
+                        };
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        fn notify<N: NotificationTrait>(&self, params: N::Params) {
+            self.connection
+                .sender
+                .send(Notification::new(N::METHOD.to_owned(), params).into())
+                .unwrap();
+        }
+
+        /// Waits for the diagnostics published after a change.
+        fn diagnostics(&self) -> PublishDiagnosticsParams {
+            loop {
+                match self.connection.receiver.recv().unwrap() {
+                    Message::Notification(n) if n.method == PublishDiagnostics::METHOD => {
+                        return serde_json::from_value(n.params).unwrap();
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        fn open(&self, text: &str) -> PublishDiagnosticsParams {
+            self.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri(),
+                    "august".to_owned(),
+                    1,
+                    text.to_owned(),
+                ),
+            });
+            self.diagnostics()
+        }
+
+        fn change(&self, text: &str) -> PublishDiagnosticsParams {
+            self.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier::new(uri(), 2),
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: text.to_owned(),
+                }],
+            });
+            self.diagnostics()
+        }
+
+        fn definition(&mut self, line: u32, character: u32) -> Option<Range> {
+            let params = GotoDefinitionParams {
+                text_document_position_params: position(line, character),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            };
+            match self.request::<GotoDefinition>(params).unwrap()? {
+                GotoDefinitionResponse::Scalar(location) => Some(location.range),
+                other => panic!("expected one location, found {other:?}"),
+            }
+        }
+
+        fn rename(&mut self, line: u32, character: u32, name: &str) -> Result<Vec<Range>, String> {
+            let params = RenameParams {
+                text_document_position: position(line, character),
+                new_name: name.to_owned(),
+                work_done_progress_params: Default::default(),
+            };
+            let edit = self.request::<Rename>(params)?.unwrap();
+            let mut ranges = edit.changes.unwrap()[&uri()]
+                .iter()
+                .inspect(|e| assert_eq!(e.new_text, name))
+                .map(|e| e.range)
+                .collect::<Vec<_>>();
+            ranges.sort_by_key(|r| r.start);
+            Ok(ranges)
+        }
+    }
+
+    impl Drop for Client {
+        fn drop(&mut self) {
+            self.request::<Shutdown>(()).unwrap();
+            self.notify::<Exit>(());
+            self.server.take().unwrap().join().unwrap().unwrap();
+        }
+    }
+
+    fn uri() -> Uri {
+        "file:///main.august".parse().unwrap()
+    }
+
+    fn position(line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(uri()),
+            Position::new(line, character),
+        )
+    }
+
+    fn range(line: u32, start: u32, end: u32) -> Range {
+        Range::new(Position::new(line, start), Position::new(line, end))
+    }
+
+    #[test]
+    fn initialize() {
+        let client = Client::start();
+        let capabilities = &client.initialize.capabilities;
+        assert_eq!(capabilities.rename_provider, Some(OneOf::Left(true)));
+        assert_eq!(capabilities.definition_provider, Some(OneOf::Left(true)));
+    }
+
+    #[test]
+    fn publishes_diagnostics() {
+        let client = Client::start();
+        let published = client.open(CODE);
+        assert_eq!(published.uri, uri());
+        assert!(published.diagnostics.is_empty());
+
+        let published = client.change(&CODE.replace("depends_on(Test)", "depends_on(Tset)"));
+        assert_eq!(published.diagnostics.len(), 1);
+        let diagnostic = &published.diagnostics[0];
+        assert_eq!(diagnostic.range, range(3, 15, 19));
+        assert!(diagnostic.message.contains("Did you mean Test?"));
+    }
+
+    #[test]
+    fn completion() {
+        let mut client = Client::start();
+        client.open(CODE);
+
+        let params = CompletionParams {
+            text_document_position: position(3, 4),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        };
+        let Some(CompletionResponse::Array(items)) = client.request::<Completion>(params).unwrap()
+        else {
+            panic!("expected completion items");
+        };
+        let labels = items.iter().map(|i| i.label.as_str()).collect::<Vec<_>>();
+        assert!(labels.contains(&"depends_on"));
+        assert!(labels.contains(&"fs"));
+        assert!(labels.contains(&"Test"));
+    }
+
+    #[test]
+    fn definition() {
+        let mut client = Client::start();
+        client.open(CODE);
+        assert_eq!(client.definition(3, 16), Some(range(6, 5, 9)));
+        assert_eq!(client.definition(0, 8), Some(range(2, 5, 10)));
+    }
+
+    #[test]
+    fn definition_with_syntax_error() {
+        let mut client = Client::start();
+        client.open(CODE);
+        let published = client.change(&CODE.replace("exec(cargo test)", "exec(cargo test"));
+        assert!(!published.diagnostics.is_empty());
+
+        assert_eq!(client.definition(3, 16), Some(range(6, 5, 9)));
+    }
+
+    #[test]
+    fn rename() {
+        let mut client = Client::start();
+        client.open(CODE);
+        assert_eq!(
+            client.rename(3, 16, "Check"),
+            Ok(vec![range(3, 15, 19), range(6, 5, 9)])
+        );
+    }
+
+    #[test]
+    fn rename_with_syntax_error() {
+        let mut client = Client::start();
+        client.open(CODE);
+        client.change(&CODE.replace("exec(cargo test)", "exec(cargo test"));
+        assert_eq!(
+            client.rename(6, 6, "Check"),
+            Ok(vec![range(3, 15, 19), range(6, 5, 9)])
+        );
+    }
+
+    #[test]
+    fn rename_to_existing_unit() {
+        let mut client = Client::start();
+        client.open(CODE);
+        assert_eq!(
+            client.rename(3, 16, "Build"),
+            Err("A unit named Build already exists".to_owned())
+        );
+    }
+}
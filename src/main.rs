@@ -1,18 +1,23 @@
 use std::{
+    collections::HashSet,
     env::set_current_dir,
-    fs::{canonicalize, read_to_string},
-    io::{stderr, stdout},
-    path::{Path, PathBuf},
+    fs::{canonicalize, read_to_string, write, File},
+    io::{self, stderr, stdout, BufWriter, IsTerminal, Write},
+    path::{absolute, Path, PathBuf},
     process::exit,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use august_build::{
-    lexer::lexer,
-    parser::parser,
-    runtime::{NotifierExt, Runtime},
-    Module, Pragma,
+    colours::{supports_colour, OwoColorizeStderrSupported},
+    diagnostics::{Diagnostic, LintFormatter},
+    fmt,
+    lint::{self, Level, LintConfig},
+    parser::{Spanned, AST},
+    runtime::{MultiNotifier, NotifierExt, OutputMode, RunReport, Runtime, UnitStatus},
+    Diagnostics, Module, ParseErrors, Pragma,
 };
-use chumsky::{Parser, Stream};
 use clap::CommandFactory;
 use cli::Cli;
 use comfy_table::{
@@ -20,19 +25,22 @@ use comfy_table::{
     presets::UTF8_FULL,
     Row, Table,
 };
+use owo_colors::Stream;
 use thiserror::Error;
 
 use crate::{
-    cli::{CLICommand, ColourSupport},
-    colours::OwoColorizeStderrSupported,
-    error::{LowerErrorFormatter, ParserErrorFormatter},
-    notifier::{LogNotifier, SilentNotifier},
+    annotate::Annotator,
+    cli::{CLICommand, ColourSupport, ErrorFormat, LogFormat, MessageFormat},
+    notifier::{JsonNotifier, LogNotifier, ProgressNotifier},
 };
 
+mod annotate;
 mod cli;
-mod colours;
-mod error;
+mod explain;
+mod junit;
+mod lsp;
 mod notifier;
+mod trace;
 
 fn main() {
     if let Err(e) = do_main() {
@@ -42,7 +50,7 @@ fn main() {
 }
 
 fn do_main() -> Result<(), CLIError> {
-    use CLICommand::{Build, Check, Completions, Info, Inspect, Run, Test};
+    use CLICommand::{Build, Check, Completions, Explain, Fmt, Info, Inspect, Lsp, Run, Test};
 
     let cli = <Cli as clap::Parser>::parse();
 
@@ -55,37 +63,93 @@ fn do_main() -> Result<(), CLIError> {
     }
 
     match cli.subcommand {
-        Check => {
-            parse_file(&cli.script)?;
+        Check {
+            deny_warnings,
+            ref allow,
+            ref warn,
+            ref deny,
+        } => {
+            let (module, code) = parse_file(&cli)?;
+
+            let mut config = LintConfig::new();
+            for (lints, level) in [
+                (allow, Level::Allow),
+                (warn, Level::Warn),
+                (deny, Level::Deny),
+            ] {
+                for lint in lints {
+                    config = config.level(*lint, level);
+                }
+            }
+            if deny_warnings {
+                config = config.deny_warnings();
+            }
+
+            let warnings = lint::lint(&module, &config);
+            if !warnings.is_empty() {
+                let f = LintFormatter::new(warnings, cli.script.to_string_lossy(), &code)
+                    .with_colour(supports_colour(Stream::Stderr));
+                report_diagnostics(&cli, &code, &|w| f.write_reports(w), f.diagnostics());
+                if f.has_errors() {
+                    Err(CLIError::Lints)?;
+                }
+            }
         }
-        Inspect => {
-            let (module, _) = parse_file(&cli.script)?;
-            inspect(&module);
+        Inspect { json } => {
+            let (module, _) = parse_file(&cli)?;
+            if json {
+                let json = serde_json::to_string_pretty(&module).map_err(CLIError::Json)?;
+                println!("{json}");
+            } else {
+                inspect(&module);
+            }
         }
         Build => {
-            let (module, code) = parse_file(&cli.script)?;
+            let (module, code) = parse_file(&cli)?;
             let this = module
                 .unit_by_pragma(Pragma::Build)
                 .ok_or(CLIError::NonExposedPragma(Pragma::Build))?
                 .clone();
-            run_unit(&cli, module, &code, &this)?;
+            run_unit(&cli, module, &code, &this, None)?;
         }
-        Test => {
-            let (module, code) = parse_file(&cli.script)?;
+        Test { ref junit } => {
+            let (module, code) = parse_file(&cli)?;
             let this = module
                 .unit_by_pragma(Pragma::Test)
                 .ok_or(CLIError::NonExposedPragma(Pragma::Test))?
                 .clone();
-            run_unit(&cli, module, &code, &this)?;
+            run_unit(&cli, module, &code, &this, junit.as_deref())?;
         }
         Run { ref unit } => {
-            let (module, code) = parse_file(&cli.script)?;
+            let (module, code) = parse_file(&cli)?;
             if module.unit_exists(unit) {
-                run_unit(&cli, module, &code, unit)?;
+                run_unit(&cli, module, &code, unit, None)?;
             } else {
                 Err(CLIError::NonExistentUnit(unit.clone()))?;
             }
         }
+        Fmt { check } => {
+            let (ast, code) = parse_ast(&cli)?;
+            let formatted = fmt::format(&ast);
+            if formatted != code {
+                if check {
+                    Err(CLIError::Unformatted(cli.script.clone()))?;
+                }
+                write(&cli.script, formatted).map_err(|io| CLIError::IO(cli.script.clone(), io))?;
+            }
+        }
+        Lsp => {
+            let (connection, io_threads) = lsp_server::Connection::stdio();
+            lsp::run(connection).map_err(|e| CLIError::Lsp(e.to_string()))?;
+            io_threads
+                .join()
+                .map_err(|e| CLIError::Lsp(e.to_string()))?;
+        }
+        Explain { ref code } => {
+            let text = explain::explanation(code)
+                .ok_or_else(|| CLIError::UnknownErrorCode(code.clone()))?;
+            println!("{text}");
+        }
         Completions { shell } => {
             clap_complete::generate(shell, &mut Cli::command(), "august", &mut stdout());
         }
@@ -117,6 +181,8 @@ enum CLIError {
     Parsing,
     #[error("An error occurred during lowering")]
     Lowering,
+    #[error("Lints were denied")]
+    Lints,
     #[error("An error occurred during runtime")]
     Runtime,
     #[error("No unit assigned to {0:?}")]
@@ -125,6 +191,14 @@ enum CLIError {
     NonExistentUnit(String),
     #[error("{0:?}: {1}")]
     IO(PathBuf, std::io::Error),
+    #[error("{0:?} isn't formatted, run `august fmt` to format it")]
+    Unformatted(PathBuf),
+    #[error("Language server failed: {0}")]
+    Lsp(String),
+    #[error("Unknown error code {0}")]
+    UnknownErrorCode(String),
+    #[error("Failed to write the module as JSON: {0}")]
+    Json(serde_json::Error),
 }
 
 fn relative_to(path: impl AsRef<Path>) -> Result<(), CLIError> {
@@ -133,18 +207,14 @@ fn relative_to(path: impl AsRef<Path>) -> Result<(), CLIError> {
             .map_err(|_| {
                 CLIError::IO(
                     path.as_ref().to_path_buf(),
-                    std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        "Path provided for script cannot be canonicalized",
-                    ),
+                    std::io::Error::other("Path provided for script cannot be canonicalized"),
                 )
             })?
             .parent()
             .ok_or_else(|| {
                 CLIError::IO(
                     path.as_ref().to_path_buf(),
-                    std::io::Error::new(
-                        std::io::ErrorKind::Other,
+                    std::io::Error::other(
                         "Path provided for script doesn't have a parent directory",
                     ),
                 )
@@ -155,71 +225,279 @@ fn relative_to(path: impl AsRef<Path>) -> Result<(), CLIError> {
     Ok(())
 }
 
-fn parse_file(src: impl AsRef<Path>) -> Result<(Module, String), CLIError> {
-    let code = read_to_string(&src).map_err(|io| CLIError::IO(src.as_ref().to_path_buf(), io))?;
-    let len = code.len();
-
-    let src_str = src.as_ref().to_string_lossy().to_string();
-
-    let tokens = lexer()
-        .parse(Stream::from_iter(
-            len..len + 1,
-            code.chars().enumerate().map(|(i, c)| (c, i..i + 1)),
-        ))
-        .map_err(|err| {
-            ParserErrorFormatter::new(err, &src_str, &code)
-                .write_reports(&mut stderr())
-                .ok();
-            CLIError::Lexing
-        })?;
-
-    let ast = parser()
-        .parse(Stream::from_iter(len..len + 1, tokens.into_iter()))
-        .map_err(|err| {
-            ParserErrorFormatter::new(err, &src_str, &code)
-                .write_reports(&mut stderr())
-                .ok();
-            CLIError::Parsing
-        })?;
-
-    Module::lower(ast)
-        .map_err(|err| {
-            LowerErrorFormatter::new(err, &src_str, &code)
-                .write_reports(&mut stderr())
-                .ok();
-            CLIError::Lowering
-        })
+/// Writes reports about the build script in the formats chosen by
+/// `--message-format` and `--error-format`.
+fn report_diagnostics(
+    cli: &Cli,
+    code: &str,
+    write_reports: &dyn Fn(&mut dyn Write) -> io::Result<()>,
+    diagnostics: Vec<Diagnostic>,
+) {
+    let src_str = cli.script.to_string_lossy();
+    match cli.message_format {
+        MessageFormat::Human => {
+            write_reports(&mut stderr()).ok();
+        }
+        MessageFormat::Json => {
+            let mut out = stdout().lock();
+            for d in &diagnostics {
+                writeln!(out, "{}", d.to_json(&src_str, code)).ok();
+            }
+        }
+    }
+    if cli.error_format != ErrorFormat::Human {
+        Annotator::new(cli.error_format, src_str, code, stdout()).annotate(diagnostics);
+    }
+}
+
+fn parse_file(cli: &Cli) -> Result<(Module, String), CLIError> {
+    let src = &cli.script;
+    let code = read_to_string(src).map_err(|io| CLIError::IO(src.clone(), io))?;
+
+    august_build::parse_str(src.to_string_lossy(), &code)
         .map(|module| (module, code))
+        .map_err(|d| report_errors(cli, d))
 }
 
-fn run_unit(cli: &Cli, module: Module, code: &str, name: &str) -> Result<(), CLIError> {
+/// Parses the build script without lowering it,
+/// so it doesn't need to refer to units that exist.
+fn parse_ast(cli: &Cli) -> Result<(Vec<AST>, String), CLIError> {
+    let src = &cli.script;
+    let code = read_to_string(src).map_err(|io| CLIError::IO(src.clone(), io))?;
+
+    august_build::parse_ast(src.to_string_lossy(), &code)
+        .map(|ast| (ast, code))
+        .map_err(|d| report_errors(cli, d))
+}
+
+/// Reports errors from parsing the build script,
+/// returning the error for the stage that failed.
+fn report_errors(cli: &Cli, d: Diagnostics) -> CLIError {
+    let err = match d.errors {
+        ParseErrors::Io(io) => return CLIError::IO(cli.script.clone(), io),
+        ParseErrors::Lexer(_) => CLIError::Lexing,
+        ParseErrors::Parser(_) => CLIError::Parsing,
+        ParseErrors::Lower(_) => CLIError::Lowering,
+    };
+
+    report_diagnostics(
+        cli,
+        &d.code,
+        &|w| d.write_reports(w, supports_colour(Stream::Stderr)),
+        d.diagnostics(),
+    );
+    err
+}
+
+fn run_unit(
+    cli: &Cli,
+    module: Module,
+    code: &str,
+    name: &str,
+    junit: Option<&Path>,
+) -> Result<(), CLIError> {
+    // Resolved before moving into the script's directory
+    let resolve = |p: &Option<PathBuf>| {
+        p.as_ref()
+            .map(|p| absolute(p).map_err(|io| CLIError::IO(p.clone(), io)))
+            .transpose()
+    };
+    let trace_path = resolve(&cli.trace)?;
+    let junit_path = resolve(&junit.map(Path::to_path_buf))?;
+    let run_log_dir = resolve(&cli.log_dir)?.map(|dir| {
+        let run_id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        dir.join(run_id.to_string())
+    });
+
     relative_to(&cli.script)?;
 
-    let runtime = if cli.quiet {
-        Runtime::new(module, SilentNotifier)
+    let output = if cli.quiet_success {
+        OutputMode::QuietSuccess
     } else {
-        Runtime::new(module, {
-            let mut n = LogNotifier::new(
-                cli.script
-                    .file_name()
-                    .map(|p| p.to_string_lossy())
-                    .unwrap_or_default(),
-                code,
-            );
-            if cli.verbose {
-                n = n.verbose();
-            }
-            n
-        })
+        cli.output.into()
     };
 
-    tokio::runtime::Runtime::new()
+    let log = {
+        let mut n = LogNotifier::new(
+            cli.script
+                .file_name()
+                .map(|p| p.to_string_lossy())
+                .unwrap_or_default(),
+            code,
+        );
+        if cli.verbose {
+            n = n.verbose();
+        }
+        if let Some(dir) = &run_log_dir {
+            n = n.log_dir(dir);
+        }
+        if output == OutputMode::QuietSuccess {
+            n = n.quiet_success();
+        }
+        n
+    };
+
+    let mut notifier = MultiNotifier::new();
+    let mut progress = None;
+    if !cli.quiet {
+        match cli.log_format {
+            LogFormat::Progress if stderr().is_terminal() => {
+                let p = Arc::new(ProgressNotifier::new(unit_invocations(&module, name), log));
+                notifier.push(Arc::clone(&p));
+                progress = Some(p);
+            }
+            LogFormat::Human | LogFormat::Progress => notifier.push(log),
+            LogFormat::Json => notifier.push(JsonNotifier::new(stderr())),
+        }
+    }
+    if cli.error_format != ErrorFormat::Human {
+        notifier.push(Annotator::new(
+            cli.error_format,
+            cli.script.to_string_lossy(),
+            code,
+            stdout(),
+        ));
+    }
+
+    let mut runtime = Runtime::new(module, notifier).with_output(output);
+    if let Some(dir) = run_log_dir {
+        runtime = runtime.with_log_dir(dir);
+    }
+    if progress.is_some() {
+        // Output is written above the live view rather than over it
+        runtime = runtime.with_output_notifications();
+    }
+    if junit_path.is_some() {
+        runtime = runtime.with_captured_output();
+    }
+
+    let report = tokio::runtime::Runtime::new()
         .unwrap()
-        .block_on(runtime.run(name))
-        .map_err(|e| {
-            runtime.notifier().error(&[e]);
-            CLIError::Runtime
-        })
+        .block_on(runtime.run(name));
+
+    if let Some(p) = progress {
+        p.finish();
+    }
+
+    // Quiet success keeps to a line per passing unit
+    if !cli.quiet && cli.log_format != LogFormat::Json && output != OutputMode::QuietSuccess {
+        summary(&report);
+    }
+
+    if let Some(path) = trace_path {
+        File::create(&path)
+            .and_then(|f| {
+                let mut w = BufWriter::new(f);
+                trace::write_trace(&report, &mut w)?;
+                w.flush()
+            })
+            .map_err(|io| CLIError::IO(path, io))?;
+    }
+
+    if let Some(path) = junit_path {
+        let suite = cli.script.file_name().unwrap_or_default().to_string_lossy();
+        File::create(&path)
+            .and_then(|f| {
+                let mut w = BufWriter::new(f);
+                junit::write_junit(&report, runtime.module(), &suite, &mut w)?;
+                w.flush()
+            })
+            .map_err(|io| CLIError::IO(path, io))?;
+    }
+
+    match report.error {
+        Some(e) => {
+            runtime.notifier().error(name, &[e]);
+            Err(CLIError::Runtime)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Counts the unit invocations that could happen as part of running `name`.
+///
+/// Dependencies only run once, while `do` runs a unit again every time it's called.
+fn unit_invocations(module: &Module, name: &str) -> usize {
+    fn count<'a>(
+        module: &'a Module,
+        name: &'a str,
+        deps_run: &mut HashSet<&'a str>,
+        calling: &mut Vec<&'a str>,
+    ) -> usize {
+        let Some(unit) = module.units().get(&Spanned::new(name.to_owned())) else {
+            return 0;
+        };
+        // A unit calling itself never finishes, so it's only counted once
+        if calling.contains(&name) {
+            return 0;
+        }
+        calling.push(name);
+
+        let mut total = 1;
+        for dep in unit.deps() {
+            if deps_run.insert(dep.inner()) {
+                total += count(module, dep.inner(), deps_run, calling);
+            }
+        }
+        for call in unit.calls() {
+            total += count(module, call.inner(), deps_run, calling);
+        }
+
+        calling.pop();
+        total
+    }
+
+    count(module, name, &mut HashSet::new(), &mut Vec::new())
+}
+
+fn summary(report: &RunReport) {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .apply_modifier(UTF8_SOLID_INNER_BORDERS)
+        .set_header(["Unit", "Status", "Duration"]);
+
+    table.add_rows(report.units.iter().map(|u| {
+        Row::from([
+            u.name.clone(),
+            match u.status {
+                UnitStatus::Completed => "Completed",
+                UnitStatus::Failed => "Failed",
+                UnitStatus::Skipped => "Skipped",
+                UnitStatus::Cached => "Cached",
+            }
+            .to_owned(),
+            format!("{:.2?}", u.duration()),
+        ])
+    }));
+
+    let critical_path = report.critical_path();
+    table.add_row(Row::from([
+        "Total".to_owned(),
+        String::new(),
+        format!("{:.2?}", report.duration()),
+    ]));
+    table.add_row(Row::from([
+        "Critical Path".to_owned(),
+        critical_path
+            .iter()
+            .map(|u| u.name.as_str())
+            .collect::<Vec<_>>()
+            .join(" -> "),
+        format!(
+            "{:.2?}",
+            critical_path
+                .first()
+                .map(|u| u.duration())
+                .unwrap_or_default()
+        ),
+    ]));
+
+    eprintln!("{table}");
 }
 
 fn inspect(module: &Module) {
@@ -1,26 +1,35 @@
-use std::io;
+use std::{
+    io::{stderr, stdout, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use crate::colours::OwoColorizeStderrSupported;
-use ariadne::{Color, Label, Report, ReportKind, Source};
+use crossterm::terminal;
+use owo_colors::Stream;
+use serde_json::{json, Value};
 
 use august_build::{
+    colours::{supports_colour, OwoColorizeStderrSupported},
+    diagnostics::RuntimeErrorFormatter,
     parser::Spanned,
-    runtime::{Notifier, NotifierEvent, RuntimeError},
+    runtime::{log_path, Notifier, NotifierEvent, RuntimeError},
     Command,
 };
 
-#[derive(Debug)]
-pub struct SilentNotifier;
-
-impl Notifier for SilentNotifier {
-    fn on_event(&self, _event: NotifierEvent<'_>) {}
-}
-
 #[derive(Debug)]
 pub struct LogNotifier {
     file_name: String,
     code: String,
     verbose: bool,
+    quiet_success: bool,
+    log_dir: Option<PathBuf>,
+    /// Failed units whose log files haven't been pointed to yet
+    failed: Mutex<Vec<String>>,
 }
 
 impl LogNotifier {
@@ -29,6 +38,9 @@ impl LogNotifier {
             file_name: file_name.into(),
             code: code.into(),
             verbose: false,
+            quiet_success: false,
+            log_dir: None,
+            failed: Mutex::default(),
         }
     }
 
@@ -38,6 +50,28 @@ impl LogNotifier {
         self
     }
 
+    /// Only logs a single line for each unit that completes,
+    /// leaving out when units start and which dependencies they spawn.
+    #[inline]
+    pub fn quiet_success(mut self) -> Self {
+        self.quiet_success = true;
+        self
+    }
+
+    /// Points to the log files of failed units when reporting errors.
+    #[inline]
+    pub fn log_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.log_dir = Some(dir.into());
+        self
+    }
+
+    /// Remembers a failed unit so its log file can be included in error reports.
+    pub fn track_failure(&self, name: &str) {
+        if self.log_dir.is_some() {
+            self.failed.lock().unwrap().push(name.to_owned());
+        }
+    }
+
     fn cmd_call(&self, cmd: &Command) {
         use august_build::EnvCommand::{PathPush, PathRemove, RemoveVar, SetVar};
         use august_build::FsCommand::{Copy, Create, CreateDir, Move, Remove};
@@ -97,99 +131,19 @@ impl LogNotifier {
     }
 
     fn err(&self, errors: &[RuntimeError]) {
-        use august_build::runtime::FsError::{
-            CopyError, CreateDirError, CreateFileError, FileAccessError, RemoveError,
-        };
-        use RuntimeError::{
-            CommandUnsupported, DependencyError, ExecutionFailure, FailedDependency, FsError,
-            JoinPathsError,
-        };
-
-        let fs_single = |p: &Spanned<String>, io: &io::Error, message: &str| {
-            Report::build(
-                ReportKind::Custom("[err]", Color::Red),
-                (self.file_name.clone(), p.span()),
-            )
-            .with_message(message)
-            .with_note(io.to_string())
-            .with_label(Label::new((self.file_name.clone(), p.span())).with_color(Color::Red))
-            .finish()
-            .eprint((self.file_name.clone(), Source::from(self.code.clone())))
+        RuntimeErrorFormatter::new(errors, &self.file_name, &self.code)
+            .with_colour(supports_colour(Stream::Stderr))
+            .write_reports(&mut stderr())
             .ok();
-        };
 
-        for err in errors {
-            match err {
-                DependencyError(span) => {
-                    eprintln!(
-                        "{} Failed to complete {} due to other errors",
-                        "[err]".red(),
-                        span.red()
-                    );
-                }
-                FailedDependency(parent, child) => {
-                    eprintln!(
-                        "{} Unable to complete unit {} due to {} failing",
-                        "[err]".red(),
-                        parent.cyan(),
-                        child.red()
-                    );
-                }
-                ExecutionFailure(args, io) => {
-                    if !args.is_empty() {
-                        let arg0 = args.first().unwrap().span();
-                        let argn = args.last().unwrap().span();
-
-                        Report::build(ReportKind::Error, (self.file_name.clone(), arg0.clone()))
-                            .with_label(
-                                Label::new((self.file_name.clone(), arg0.start..argn.end))
-                                    .with_color(Color::Red),
-                            )
-                            .with_message("Failed to execute process")
-                            .with_note(io.to_string())
-                            .finish()
-                            .eprint((self.file_name.clone(), Source::from(self.code.clone())))
-                            .ok();
-                    }
-                }
-                FsError(CreateFileError(p, io)) => {
-                    fs_single(p, io, "Failed to create file");
-                }
-                FsError(CreateDirError(p, io)) => {
-                    fs_single(p, io, "Failed to create directory");
-                }
-                FsError(RemoveError(p, io)) => {
-                    fs_single(p, io, "Failed to remove file/directory");
-                }
-                FsError(FileAccessError(p, io)) => {
-                    fs_single(p, io, "Unable to read the file contents");
-                }
-                FsError(CopyError(src, dst, io)) => {
-                    Report::build(
-                        ReportKind::Custom("[err]", Color::Red),
-                        (self.file_name.clone(), src.span()),
-                    )
-                    .with_message(format!("Unable copy {} to {}", src.cyan(), dst.cyan()))
-                    .with_note(io.to_string())
-                    .with_label(
-                        Label::new((self.file_name.clone(), src.span())).with_color(Color::Red),
-                    )
-                    .with_label(
-                        Label::new((self.file_name.clone(), dst.span())).with_color(Color::Red),
-                    )
-                    .finish()
-                    .eprint((self.file_name.clone(), Source::from(self.code.clone())))
-                    .ok();
-                }
-                JoinPathsError(e) => {
-                    eprintln!("{} Error occured when join to PATH: {e}", "[err]".red());
-                }
-                CommandUnsupported(cmd) => {
-                    eprintln!(
-                        "{} Command {cmd:?} is unsupported on the current runtime",
-                        "[err]".red()
-                    );
-                }
+        if let Some(dir) = &self.log_dir {
+            for unit in self.failed.lock().unwrap().drain(..) {
+                eprintln!(
+                    "{} Full output of unit {} written to {}",
+                    "[log]".cyan(),
+                    unit.red(),
+                    log_path(dir, &unit).display()
+                );
             }
         }
     }
@@ -198,15 +152,41 @@ impl LogNotifier {
 impl Notifier for LogNotifier {
     fn on_event(&self, event: NotifierEvent<'_>) {
         match event {
-            NotifierEvent::Call(cmd) => self.cmd_call(cmd),
-            NotifierEvent::Start(name) => {
+            NotifierEvent::Call { cmd, .. } => self.cmd_call(cmd),
+            NotifierEvent::CommandComplete {
+                cmd: cmd @ Command::Exec(_),
+                duration,
+                status,
+                ..
+            } if self.verbose => {
+                eprintln!(
+                    "{} Finished {cmd} in {duration:.2?}{}",
+                    "[cmd]".blue(),
+                    status
+                        .exit_code()
+                        .map(|c| format!(" with exit code {c}"))
+                        .unwrap_or_default()
+                );
+            }
+            NotifierEvent::Start(name) if !self.quiet_success => {
                 eprintln!("{} Begining work on unit {}", "[run]".green(), name.green());
             }
             NotifierEvent::Complete(name) => {
                 eprintln!("{} Completed unit {}", "[run]".green(), name.green());
             }
-            NotifierEvent::Error(err) => self.err(err),
-            NotifierEvent::Dependency { parent, name } => {
+            NotifierEvent::UnitFailed(name) => {
+                self.track_failure(name);
+                eprintln!("{} Unit {} failed", "[run]".red(), name.red());
+            }
+            NotifierEvent::UnitSkipped(name) => {
+                eprintln!(
+                    "{} Skipped commands of unit {} as a dependency failed",
+                    "[run]".yellow(),
+                    name.yellow()
+                );
+            }
+            NotifierEvent::Error { errors, .. } => self.err(errors),
+            NotifierEvent::Dependency { parent, name } if !self.quiet_success => {
                 eprintln!(
                     "{} Spawning unit {} to resolve dependency of {}",
                     "[dep]".yellow(),
@@ -214,7 +194,7 @@ impl Notifier for LogNotifier {
                     parent.yellow()
                 );
             }
-            NotifierEvent::BlockOn { parent, name } => {
+            NotifierEvent::BlockOn { parent, name } if !self.quiet_success => {
                 eprintln!(
                     "{} Blocking {} until {} reaches completion",
                     "[dep]".yellow(),
@@ -222,6 +202,400 @@ impl Notifier for LogNotifier {
                     name.yellow()
                 );
             }
+            // Only logged for `exec` in verbose mode
+            NotifierEvent::CommandComplete { .. } => {}
+            // Left out with `quiet_success`
+            NotifierEvent::Start(_)
+            | NotifierEvent::Dependency { .. }
+            | NotifierEvent::BlockOn { .. } => {}
+            // Only sent to notifiers drawing a live view
+            NotifierEvent::Output { .. } => {}
+            // Events added to the library after this notifier
+            _ => {}
+        }
+    }
+}
+
+/// Writes each event as a single line JSON object.
+pub struct JsonNotifier {
+    out: Mutex<Box<dyn Write + Send>>,
+    started: Instant,
+}
+
+impl JsonNotifier {
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        Self {
+            out: Mutex::new(Box::new(out)),
+            started: Instant::now(),
+        }
+    }
+
+    fn emit(&self, at: Instant, event: &str, mut fields: Value) {
+        let timestamp = (SystemTime::now() - at.elapsed())
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        fields["event"] = event.into();
+        fields["timestamp"] = timestamp.into();
+        fields["elapsed"] = at
+            .saturating_duration_since(self.started)
+            .as_secs_f64()
+            .into();
+
+        let mut out = self.out.lock().unwrap();
+        writeln!(out, "{fields}").ok();
+    }
+}
+
+impl Notifier for JsonNotifier {
+    fn on_event(&self, event: NotifierEvent<'_>) {
+        self.on_event_at(Instant::now(), event);
+    }
+
+    fn on_event_at(&self, at: Instant, event: NotifierEvent<'_>) {
+        match event {
+            NotifierEvent::Call { unit, cmd } => self.emit(
+                at,
+                "call",
+                json!({ "unit": unit, "command": cmd.to_string() }),
+            ),
+            NotifierEvent::CommandComplete {
+                unit,
+                cmd,
+                duration,
+                status,
+            } => self.emit(
+                at,
+                "command_complete",
+                json!({
+                    "unit": unit,
+                    "command": cmd.to_string(),
+                    "duration": duration.as_secs_f64(),
+                    "success": status.is_success(),
+                    "exit_code": status.exit_code(),
+                }),
+            ),
+            NotifierEvent::Start(name) => self.emit(at, "start", json!({ "unit": name })),
+            NotifierEvent::Complete(name) => self.emit(at, "complete", json!({ "unit": name })),
+            NotifierEvent::UnitFailed(name) => {
+                self.emit(at, "unit_failed", json!({ "unit": name }));
+            }
+            NotifierEvent::UnitSkipped(name) => {
+                self.emit(at, "unit_skipped", json!({ "unit": name }));
+            }
+            NotifierEvent::Error { unit, errors } => self.emit(
+                at,
+                "error",
+                json!({
+                    "unit": unit,
+                    "errors": errors
+                        .iter()
+                        .map(|e| json!({ "message": e.to_string() }))
+                        .collect::<Vec<_>>()
+                }),
+            ),
+            NotifierEvent::Dependency { parent, name } => {
+                self.emit(at, "dependency", json!({ "parent": parent, "unit": name }));
+            }
+            NotifierEvent::BlockOn { parent, name } => {
+                self.emit(at, "block_on", json!({ "parent": parent, "unit": name }));
+            }
+            // Only sent to notifiers drawing a live view
+            NotifierEvent::Output { .. } => {}
+            // Events added to the library after this notifier
+            _ => {}
+        }
+    }
+}
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Redraws a live view of running units and commands on stderr.
+///
+/// Should only be used when stderr is a terminal,
+/// errors are passed on to a [`LogNotifier`] for reporting.
+pub struct ProgressNotifier {
+    state: Arc<Mutex<Progress>>,
+    log: LogNotifier,
+    done: Arc<AtomicBool>,
+    redraw: Mutex<Option<JoinHandle<()>>>,
+}
+
+struct Progress {
+    started: Instant,
+    total: usize,
+    completed: usize,
+    failed: usize,
+    skipped: usize,
+    running: Vec<RunningUnit>,
+    /// Number of lines drawn by the last redraw
+    drawn: usize,
+    frame: usize,
+}
+
+struct RunningUnit {
+    /// Tells apart invocations of the same unit, see [`Notifier::on_unit_event_at`]
+    invocation: Option<usize>,
+    name: String,
+    started: Instant,
+    command: Option<String>,
+}
+
+impl Progress {
+    fn clear(&mut self, w: &mut impl Write) {
+        if self.drawn > 0 {
+            write!(w, "\x1b[{}A\x1b[J", self.drawn).ok();
+            self.drawn = 0;
+        }
+    }
+
+    fn draw(&mut self, w: &mut impl Write) {
+        self.clear(w);
+        self.frame = self.frame.wrapping_add(1);
+        let spinner = SPINNER[self.frame % SPINNER.len()];
+        // Lines are kept narrower than the terminal,
+        // as lines that wrap take more rows than `clear` moves up
+        let width = terminal::size().map_or(80, |(cols, _)| usize::from(cols).saturating_sub(1));
+
+        for unit in &self.running {
+            let elapsed = format!("{:.1?}", unit.started.elapsed());
+            let name = truncate(&unit.name, width.saturating_sub(elapsed.len() + 4));
+            let cmd = truncate(
+                unit.command.as_deref().unwrap_or_default(),
+                width
+                    .saturating_sub(name.chars().count() + elapsed.len() + 4)
+                    .min(60),
+            );
+            writeln!(
+                w,
+                "{} {} {elapsed} {}",
+                spinner.cyan(),
+                name.green(),
+                cmd.bright_black()
+            )
+            .ok();
+        }
+
+        let mut status = format!(
+            "[{}/{}] units complete",
+            self.completed + self.failed + self.skipped,
+            self.total
+        );
+        if self.failed > 0 {
+            status += &format!(", {} failed", self.failed);
+        }
+        if self.skipped > 0 {
+            status += &format!(", {} skipped", self.skipped);
+        }
+        let elapsed = format!("{:.1?}", self.started.elapsed());
+        let status = truncate(&status, width.saturating_sub(elapsed.len() + 7));
+        writeln!(w, "{} {status} {elapsed}", "[run]".green()).ok();
+
+        self.drawn = self.running.len() + 1;
+        w.flush().ok();
+    }
+
+    /// Finds a running unit by its invocation, or only by name for events without one.
+    fn unit(&mut self, invocation: Option<usize>, name: &str) -> Option<&mut RunningUnit> {
+        self.running
+            .iter_mut()
+            .find(|u| u.name == name && (invocation.is_none() || u.invocation == invocation))
+    }
+
+    fn finish_unit(&mut self, invocation: Option<usize>, name: &str) -> Option<Duration> {
+        let i = self
+            .running
+            .iter()
+            .position(|u| u.name == name && (invocation.is_none() || u.invocation == invocation))?;
+        Some(self.running.remove(i).started.elapsed())
+    }
+}
+
+/// Shortens `text` to at most `max` characters, ending with `...` if it's cut short.
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_owned()
+    } else if max <= 3 {
+        text.chars().take(max).collect()
+    } else {
+        text.chars().take(max - 3).collect::<String>() + "..."
+    }
+}
+
+impl ProgressNotifier {
+    /// Creates a [`ProgressNotifier`] expecting `total` unit invocations,
+    /// which starts redrawing immediately.
+    pub fn new(total: usize, log: LogNotifier) -> Self {
+        let state = Arc::new(Mutex::new(Progress {
+            started: Instant::now(),
+            total,
+            completed: 0,
+            failed: 0,
+            skipped: 0,
+            running: Vec::new(),
+            drawn: 0,
+            frame: 0,
+        }));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let redraw = {
+            let state = Arc::clone(&state);
+            let done = Arc::clone(&done);
+            thread::spawn(move || {
+                while !done.load(Ordering::Acquire) {
+                    state.lock().unwrap().draw(&mut stderr().lock());
+                    thread::sleep(REDRAW_INTERVAL);
+                }
+            })
+        };
+
+        Self {
+            state,
+            log,
+            done,
+            redraw: Mutex::new(Some(redraw)),
+        }
+    }
+
+    /// Stops redrawing, leaving a final status line.
+    ///
+    /// Called on drop if it hasn't been already.
+    pub fn finish(&self) {
+        let Some(redraw) = self.redraw.lock().unwrap().take() else {
+            return;
+        };
+        self.done.store(true, Ordering::Release);
+        redraw.join().ok();
+
+        let mut state = self.state.lock().unwrap();
+        state.running.clear();
+        state.draw(&mut stderr().lock());
+    }
+
+    /// Prints a line above the live view.
+    fn println(&self, state: &mut Progress, line: std::fmt::Arguments<'_>) {
+        let mut w = stderr().lock();
+        state.clear(&mut w);
+        writeln!(w, "{line}").ok();
+        if !self.done.load(Ordering::Acquire) {
+            state.draw(&mut w);
+        }
+    }
+
+    /// Writes process output above the live view.
+    fn write_output(&self, state: &mut Progress, text: &[u8], to_stderr: bool) {
+        let mut w = stderr().lock();
+        state.clear(&mut w);
+        if to_stderr {
+            w.write_all(text).ok();
+        } else {
+            let mut out = stdout().lock();
+            out.write_all(text).ok();
+            out.flush().ok();
+        }
+        if !self.done.load(Ordering::Acquire) {
+            state.draw(&mut w);
+        }
+    }
+}
+
+impl Drop for ProgressNotifier {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+impl Notifier for ProgressNotifier {
+    fn on_event(&self, event: NotifierEvent<'_>) {
+        self.on_event_at(Instant::now(), event);
+    }
+
+    fn on_event_at(&self, at: Instant, event: NotifierEvent<'_>) {
+        self.handle(at, None, event);
+    }
+
+    fn on_unit_event_at(&self, at: Instant, invocation: usize, event: NotifierEvent<'_>) {
+        self.handle(at, Some(invocation), event);
+    }
+}
+
+impl ProgressNotifier {
+    fn handle(&self, at: Instant, invocation: Option<usize>, event: NotifierEvent<'_>) {
+        let mut state = self.state.lock().unwrap();
+
+        match event {
+            NotifierEvent::Start(name) => state.running.push(RunningUnit {
+                invocation,
+                name: name.to_owned(),
+                started: at,
+                command: None,
+            }),
+            NotifierEvent::Call { unit, cmd } => {
+                if matches!(cmd, Command::Do(_) | Command::Concurrent(_)) {
+                    return;
+                }
+                if let Some(u) = state.unit(invocation, unit) {
+                    u.command = Some(cmd.to_string());
+                }
+            }
+            NotifierEvent::CommandComplete { unit, cmd, .. } => {
+                let cmd = cmd.to_string();
+                if let Some(u) = state
+                    .unit(invocation, unit)
+                    .filter(|u| u.command.as_ref() == Some(&cmd))
+                {
+                    u.command = None;
+                }
+            }
+            NotifierEvent::Complete(name) => {
+                state.completed += 1;
+                if let Some(elapsed) = state.finish_unit(invocation, name) {
+                    self.println(
+                        &mut state,
+                        format_args!(
+                            "{} Completed unit {} in {elapsed:.2?}",
+                            "[run]".green(),
+                            name.green()
+                        ),
+                    );
+                }
+            }
+            NotifierEvent::UnitFailed(name) => {
+                state.failed += 1;
+                state.finish_unit(invocation, name);
+                self.log.track_failure(name);
+                self.println(
+                    &mut state,
+                    format_args!("{} Unit {} failed", "[run]".red(), name.red()),
+                );
+            }
+            NotifierEvent::UnitSkipped(name) => {
+                state.skipped += 1;
+                state.finish_unit(invocation, name);
+                self.println(
+                    &mut state,
+                    format_args!(
+                        "{} Skipped commands of unit {} as a dependency failed",
+                        "[run]".yellow(),
+                        name.yellow()
+                    ),
+                );
+            }
+            NotifierEvent::Error { .. } => {
+                state.clear(&mut stderr().lock());
+                self.log.on_event(event);
+                if !self.done.load(Ordering::Acquire) {
+                    state.draw(&mut stderr().lock());
+                }
+            }
+            NotifierEvent::Output { text, stderr, .. } => {
+                self.write_output(&mut state, text, stderr);
+            }
+            NotifierEvent::Dependency { .. } | NotifierEvent::BlockOn { .. } => {}
+            // Events added to the library after this notifier
+            _ => {}
         }
     }
 }
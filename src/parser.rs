@@ -1,3 +1,6 @@
+// chumsky's select! expands to closures returning Result<_, Simple<Token>>
+#![allow(clippy::result_large_err)]
+
 use chumsky::{combinator::DelimitedBy, prelude::*, primitive::Just};
 use std::{fmt::Display, hash::Hash, ops::Range};
 
@@ -60,6 +63,60 @@ impl<T: Hash> Hash for Spanned<T> {
     }
 }
 
+/// How [`Spanned`] is serialized, as `{ "value": ..., "span": { "start": ..., "end": ... } }`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "Spanned")]
+struct SpannedRepr<T> {
+    value: T,
+    span: Range<usize>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for Spanned<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SpannedRepr {
+            value: &self.0,
+            span: self.1.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Spanned<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        SpannedRepr::deserialize(deserializer).map(|r| Self(r.value, r.span))
+    }
+}
+
+/// Keywords used outside of units.
+pub const KEYWORDS: &[&str] = &["unit", "expose", "as"];
+/// Commands that aren't part of a module, kept in sync with [`command`].
+pub const CORE_COMMANDS: &[&str] = &["depends_on", "do", "meta", "exec", "concurrent"];
+/// Modules and the commands they contain, kept in sync with [`command`].
+pub const MODULES: &[(&str, &[&str])] = &[
+    (
+        "fs",
+        &[
+            "create",
+            "create_dir",
+            "remove",
+            "copy",
+            "copy_to",
+            "move",
+            "move_to",
+            "print_file",
+            "eprint_file",
+        ],
+    ),
+    ("io", &["println", "print", "eprintln", "eprint"]),
+    (
+        "env",
+        &["set_var", "remove_var", "path_push", "path_remove"],
+    ),
+];
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AST {
     Expose(Pragma, Spanned<String>),
@@ -69,14 +126,10 @@ pub enum AST {
 
 pub fn parser() -> impl Parser<Token, Vec<AST>, Error = Simple<Token>> {
     choice((expose(), unit()))
-        .recover_with(skip_parser(
-            choice((expose().ignored(), unit().ignored()))
-                .not()
-                .repeated()
-                .ignore_then(any().rewind())
-                .to(AST::Err),
-        ))
+        // Skips to the next expose or unit so the errors in it are reported too
+        .recover_with(skip_until([Token::Expose, Token::Unit], |_| AST::Err).skip_start())
         .repeated()
+        .then_ignore(end())
 }
 
 pub fn expose() -> impl Parser<Token, AST, Error = Simple<Token>> + Clone {
@@ -147,6 +200,7 @@ pub fn command() -> impl Parser<Token, Command, Error = Simple<Token>> {
                     .curly_delimited()
                     .map(Command::Concurrent),
             ),
+            custom_command(),
         ))
     })
     .labelled("command call")
@@ -243,6 +297,24 @@ fn env_command() -> impl Parser<Token, EnvCommand, Error = Simple<Token>> {
     ))
 }
 
+/// Commands from modules that aren't built in, checked against a registry when lowering.
+fn custom_command() -> impl Parser<Token, Command, Error = Simple<Token>> {
+    select! { |span| Token::Ident(i) if !is_builtin_module(&i) => Spanned(i, span) }
+        .then_ignore(just(Token::DoubleColon))
+        .then(ident())
+        .then(
+            str()
+                .separated_by(just(Token::Comma))
+                .allow_trailing()
+                .round_delimited(),
+        )
+        .map(|((module, name), args)| Command::Custom(module, name, args))
+}
+
+pub(crate) fn is_builtin_module(name: &str) -> bool {
+    MODULES.iter().any(|(m, _)| m.eq_ignore_ascii_case(name))
+}
+
 fn module_prefix(s: impl AsRef<str>) -> impl Parser<Token, (), Error = Simple<Token>> {
     select! { |span| Token::Ident(i) if i.eq_ignore_ascii_case(s.as_ref())  => Spanned(i, span) }
         .ignored()
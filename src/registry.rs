@@ -0,0 +1,224 @@
+//! Commands written in Rust that build scripts call like the built-in modules.
+//!
+//! ```
+//! use august_build::registry::CommandRegistry;
+//!
+//! let mut registry = CommandRegistry::new();
+//! registry.module("docker")?.command("build", |call| async move {
+//!     println!("Building image {}", call.args()[0]);
+//!     Ok(())
+//! });
+//!
+//! assert!(registry.contains("DOCKER", "build"));
+//! assert!(registry.module("fs").is_err());
+//! # Ok::<(), august_build::registry::BuiltinModule>(())
+//! ```
+//!
+//! The build script can then call `DOCKER::build("app")` once lowered with
+//! [`Module::lower_with`](crate::Module::lower_with) and run by a
+//! [`Runtime::with_registry`](crate::runtime::Runtime::with_registry).
+
+use std::{
+    error::Error,
+    ffi::{OsStr, OsString},
+    fmt::Debug,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+};
+
+use thiserror::Error;
+
+use crate::{parser::is_builtin_module, HashMap};
+
+/// Error returned by a command handler, reported as a runtime error of the unit that called it.
+pub type HandlerError = Box<dyn Error + Send + Sync>;
+
+pub type HandlerFuture = Pin<Box<dyn Future<Output = Result<(), HandlerError>> + Send>>;
+
+type Handler = Arc<dyn Fn(Call) -> HandlerFuture + Send + Sync>;
+
+/// A command called from a build script, passed to its handler.
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub(crate) unit: String,
+    pub(crate) args: Vec<String>,
+    pub(crate) env: Arc<HashMap<OsString, OsString>>,
+}
+
+impl Call {
+    /// Name of the unit the command was called from.
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Environment variables as set by `env` commands so far,
+    /// for passing to processes the handler starts.
+    pub fn env(&self) -> impl Iterator<Item = (&OsStr, &OsStr)> {
+        self.env.iter().map(|(k, v)| (k.as_os_str(), v.as_os_str()))
+    }
+}
+
+/// Custom commands grouped by module.
+///
+/// Module names are case insensitive, the same as `fs`, `io` and `env`,
+/// while command names must match exactly.
+/// Modules can't have the same name as one of the built-in modules.
+#[derive(Clone, Default)]
+pub struct CommandRegistry {
+    modules: HashMap<String, RegistryModule>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets a module to add commands to, creating it if it doesn't exist yet.
+    ///
+    /// Fails if `name` is a built-in module such as `fs`,
+    /// as build scripts always call the built-in commands instead.
+    pub fn module(&mut self, name: impl AsRef<str>) -> Result<&mut RegistryModule, BuiltinModule> {
+        let name = name.as_ref();
+        if is_builtin_module(name) {
+            return Err(BuiltinModule(name.to_owned()));
+        }
+
+        Ok(self.modules.entry(name.to_ascii_lowercase()).or_default())
+    }
+
+    pub fn contains(&self, module: &str, command: &str) -> bool {
+        self.handler(module, command).is_some()
+    }
+
+    /// Names of every registered module, in lowercase.
+    pub fn modules(&self) -> impl Iterator<Item = &str> {
+        self.modules.keys().map(String::as_str)
+    }
+
+    /// Names of the commands in a module, empty if it isn't registered.
+    pub fn commands(&self, module: &str) -> impl Iterator<Item = &str> {
+        self.modules
+            .get(&module.to_ascii_lowercase())
+            .into_iter()
+            .flat_map(|m| m.commands.keys().map(String::as_str))
+    }
+
+    pub(crate) fn handler(&self, module: &str, command: &str) -> Option<&Handler> {
+        self.modules
+            .get(&module.to_ascii_lowercase())?
+            .commands
+            .get(command)
+    }
+}
+
+impl Debug for CommandRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(
+                self.modules
+                    .iter()
+                    .map(|(name, module)| (name, module.commands.keys().collect::<Vec<_>>())),
+            )
+            .finish()
+    }
+}
+
+/// Returned by [`CommandRegistry::module`] for the name of a built-in module.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{0} is a built-in module, custom commands can't be added to it")]
+pub struct BuiltinModule(pub String);
+
+#[derive(Clone, Default)]
+pub struct RegistryModule {
+    commands: HashMap<String, Handler>,
+}
+
+impl RegistryModule {
+    /// Adds a command, replacing any other command with the same name.
+    ///
+    /// The handler is called each time a build script calls the command,
+    /// and runs alongside other units like any other command.
+    pub fn command<F, Fut>(&mut self, name: impl Into<String>, handler: F) -> &mut Self
+    where
+        F: Fn(Call) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), HandlerError>> + Send + 'static,
+    {
+        self.commands.insert(
+            name.into(),
+            Arc::new(move |call| Box::pin(handler(call)) as HandlerFuture),
+        );
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{BuiltinModule, CommandRegistry};
+    use crate::{
+        parse_ast,
+        runtime::{MultiNotifier, Runtime},
+        Module,
+    };
+
+    const CODE: &str = "unit Image {\n    DOCKER::build(\"app\")\n}\n";
+
+    #[test]
+    fn runs_registered_commands() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut registry = CommandRegistry::new();
+        registry.module("docker").unwrap().command("build", {
+            let calls = Arc::clone(&calls);
+            move |call| {
+                let calls = Arc::clone(&calls);
+                async move {
+                    calls
+                        .lock()
+                        .unwrap()
+                        .push((call.unit().to_owned(), call.args().to_vec()));
+                    Ok(())
+                }
+            }
+        });
+
+        let ast = parse_ast("main.august", CODE).unwrap();
+        let module = Module::lower_with(ast, &registry).unwrap();
+        let rt = Runtime::new(module, MultiNotifier::default()).with_registry(registry);
+        let report = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(rt.run("Image"));
+
+        assert!(report.is_success(), "{:?}", report.error);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [("Image".to_owned(), vec!["app".to_owned()])]
+        );
+    }
+
+    #[test]
+    fn rejects_unregistered_commands() {
+        let ast = parse_ast("main.august", CODE).unwrap();
+        let errors = Module::lower(ast).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code(), "A0107");
+    }
+
+    #[test]
+    fn rejects_builtin_modules() {
+        let mut registry = CommandRegistry::new();
+
+        assert_eq!(
+            registry.module("FS").err(),
+            Some(BuiltinModule("FS".to_owned()))
+        );
+        assert_eq!(registry.modules().count(), 0);
+    }
+}
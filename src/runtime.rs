@@ -4,19 +4,38 @@ use std::{
     fs::canonicalize,
     future::Future,
     io,
-    path::Path,
-    process::Output,
-    sync::atomic::{AtomicU8, Ordering},
+    ops::Range,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Output},
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
     task::Poll,
+    time::{Duration, Instant},
 };
 
 use arc_swap::ArcSwap;
 use dircpy::copy_dir;
 use futures::{future::ready, stream::FuturesUnordered, StreamExt, TryStreamExt};
 use thiserror::Error;
-use tokio::task::block_in_place;
+use tokio::{sync::Mutex, task::block_in_place};
 
-use crate::{parser::Spanned, Command, EnvCommand, FsCommand, HashMap, IoCommand, Module, Unit};
+use crate::{
+    parser::Spanned,
+    registry::{Call, CommandRegistry, HandlerError},
+    Command, EnvCommand, FsCommand, HashMap, IoCommand, Module, Unit,
+};
+
+pub use logs::log_path;
+use logs::UnitLogs;
+pub use output::{OutputMode, UnknownOutputMode};
+use report::Recorder;
+pub use report::{CommandReport, CommandStatus, Edge, EdgeKind, RunReport, UnitReport, UnitStatus};
+
+mod logs;
+mod output;
+mod report;
 
 #[derive(Debug, Error)]
 pub enum RuntimeError {
@@ -26,19 +45,80 @@ pub enum RuntimeError {
     FailedDependency(String, Spanned<String>),
     #[error("Failed to execute {0:?}: {1}")]
     ExecutionFailure(Vec<Spanned<String>>, io::Error),
+    /// Same as [`RuntimeError::ExecutionFailure`] for a process run with [`OutputMode::QuietSuccess`],
+    /// with the output that wasn't shown while it ran
+    #[error("Failed to execute {0:?}: {1}")]
+    ExecutionFailureWithOutput(Vec<Spanned<String>>, io::Error, String),
     #[error("{0}")]
     FsError(FsError),
     #[error("{0}")]
     JoinPathsError(env::JoinPathsError),
     #[error("Command {0:?} isn't supported on this runtime")]
     CommandUnsupported(Command),
+    /// Module and name of the custom command, along with the error from its handler
+    #[error("{0}::{1} failed: {2}")]
+    CustomCommandFailure(Spanned<String>, Spanned<String>, HandlerError),
+}
+
+impl RuntimeError {
+    /// Stable code identifying the kind of error, see `august explain`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::DependencyError(_) => "A0201",
+            Self::FailedDependency(..) => "A0202",
+            Self::ExecutionFailure(..) | Self::ExecutionFailureWithOutput(..) => "A0203",
+            Self::FsError(e) => e.code(),
+            Self::JoinPathsError(_) => "A0204",
+            Self::CommandUnsupported(_) => "A0205",
+            Self::CustomCommandFailure(..) => "A0206",
+        }
+    }
+
+    /// Location in the build script that the error relates to, if any.
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            Self::DependencyError(unit) => Some(unit.span()),
+            Self::FailedDependency(_, dep) => Some(dep.span()),
+            Self::ExecutionFailure(args, _) | Self::ExecutionFailureWithOutput(args, ..) => {
+                Some(args.first()?.span().start..args.last()?.span().end)
+            }
+            Self::FsError(e) => Some(e.span()),
+            Self::CommandUnsupported(Command::Custom(module, name, args)) => {
+                Some(module.span().start..args.last().unwrap_or(name).span().end)
+            }
+            Self::CustomCommandFailure(module, name, _) => {
+                Some(module.span().start..name.span().end)
+            }
+            Self::JoinPathsError(_) | Self::CommandUnsupported(_) => None,
+        }
+    }
 }
 
 pub struct Runtime {
     module: Module,
     notifier: Box<dyn Notifier + Sync>,
     once: HashMap<Spanned<String>, AtomicU8>,
+    locks: HashMap<String, Mutex<()>>,
     env_vars: ArcSwap<HashMap<OsString, OsString>>,
+    output: OutputMode,
+    logs: Option<UnitLogs>,
+    capture: bool,
+    notify_output: bool,
+    registry: CommandRegistry,
+}
+
+/// The unit invocation a command is called from.
+#[derive(Debug, Clone, Copy)]
+pub struct UnitScope<'a> {
+    name: &'a str,
+    id: usize,
+    recorder: &'a Recorder,
+}
+
+impl UnitScope<'_> {
+    pub fn name(&self) -> &str {
+        self.name
+    }
 }
 
 const UOS_INCOMPLETE: u8 = 0;
@@ -54,20 +134,106 @@ impl Runtime {
             .map(|name| (name.clone(), AtomicU8::new(UOS_INCOMPLETE)))
             .collect();
 
+        let locks = module
+            .units
+            .values()
+            .filter_map(Unit::lock)
+            .map(|name| (name.to_owned(), Mutex::new(())))
+            .collect();
+
         let env_vars = ArcSwap::from_pointee(env::vars_os().collect());
 
         Self {
             module,
             notifier: Box::new(notifier),
             once,
+            locks,
             env_vars,
+            output: OutputMode::default(),
+            logs: None,
+            capture: false,
+            notify_output: false,
+            registry: CommandRegistry::default(),
         }
     }
 
+    /// Writes the events and `exec` output of each unit to a log file in `dir`,
+    /// see [`log_path`].
+    ///
+    /// Output from `exec` is still written to the terminal,
+    /// but is piped through August to copy it to the log.
+    #[inline]
+    pub fn with_log_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.logs = Some(UnitLogs::new(dir.into()));
+        self
+    }
+
+    /// Sets how output from `exec` is written
+    /// for units that don't set `@output` themselves.
+    #[inline]
+    pub fn with_output(mut self, output: OutputMode) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Keeps the output of every `exec` call in its [`CommandReport`].
+    ///
+    /// Output from `exec` is still written according to the [`OutputMode`],
+    /// but is piped through August to keep a copy.
+    #[inline]
+    pub fn with_captured_output(mut self) -> Self {
+        self.capture = true;
+        self
+    }
+
+    /// Sends the output of `exec` to the notifier as [`NotifierEvent::Output`]
+    /// instead of writing it to stdout and stderr,
+    /// for notifiers that draw to the terminal and have to place output themselves.
+    ///
+    /// Processes are piped through August, even with [`OutputMode::Inherit`].
+    #[inline]
+    pub fn with_output_notifications(mut self) -> Self {
+        self.notify_output = true;
+        self
+    }
+
+    /// Runs custom commands with the handlers in `registry`,
+    /// the module should be lowered with the same registry.
+    #[inline]
+    pub fn with_registry(mut self, registry: CommandRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    pub fn registry_mut(&mut self) -> &mut CommandRegistry {
+        &mut self.registry
+    }
+
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
     pub fn notifier(&self) -> &dyn Notifier {
         &*self.notifier
     }
 
+    /// Where runtime events go, the notifier and the unit logs if there are any.
+    fn events(&self) -> Events<'_> {
+        Events {
+            notifier: &*self.notifier,
+            logs: self.logs.as_ref(),
+            invocation: None,
+        }
+    }
+
+    /// Same as [`Runtime::events`] for the events of a single invocation of a unit.
+    fn unit_events(&self, scope: UnitScope<'_>) -> Events<'_> {
+        Events {
+            invocation: Some(scope.id),
+            ..self.events()
+        }
+    }
+
     fn get_unit(&self, name: impl Into<String>) -> (&Spanned<String>, &Unit) {
         self.module
             .units
@@ -79,12 +245,26 @@ impl Runtime {
         self.once.get(&Spanned::new(name.into())).unwrap()
     }
 
-    pub async fn run(&self, unit_name: &str) -> Result<(), RuntimeError> {
+    /// Runs a unit and its dependencies,
+    /// reporting on every unit and command that was involved.
+    pub async fn run(&self, unit_name: &str) -> RunReport {
+        let started = Instant::now();
+        let recorder = Recorder::default();
+        let res = self.run_unit(unit_name, &recorder).await;
+        recorder.into_report(started, res.err())
+    }
+
+    async fn run_unit(&self, unit_name: &str, recorder: &Recorder) -> Result<(), RuntimeError> {
         // Box::pin because recursive generators are hard
         Box::pin(async {
             let (unit_span, unit) = self.get_unit(unit_name);
 
-            self.notifier.start(unit_name);
+            let scope = UnitScope {
+                name: unit_name,
+                id: recorder.start(unit_name),
+                recorder,
+            };
+            self.unit_events(scope).start(unit_name);
 
             if !unit.depends_on.is_empty() {
                 let futs = unit
@@ -101,11 +281,12 @@ impl Runtime {
                             );
                             match uos {
                                 Ok(_) => {
-                                    self.notifier.dependency(unit_name, dep.inner());
-                                    match self.run(dep.inner()).await {
+                                    self.events().dependency(unit_name, dep.inner());
+                                    recorder.edge(unit_name, dep.inner(), EdgeKind::Dependency);
+                                    match self.run_unit(dep.inner(), recorder).await {
                                         Err(e) => {
                                             uos_state.store(UOS_FAILED, Ordering::Release);
-                                            Err(e)
+                                            Err((dep.inner().as_str(), e))
                                         }
                                         Ok(o) => {
                                             uos_state.store(UOS_COMPLETE, Ordering::Release);
@@ -113,34 +294,71 @@ impl Runtime {
                                         }
                                     }
                                 }
-                                Err(UOS_FAILED) => Err(RuntimeError::FailedDependency(
-                                    unit_name.to_owned(),
-                                    dep.clone(),
+                                Err(UOS_FAILED) => Err((
+                                    unit_name,
+                                    RuntimeError::FailedDependency(
+                                        unit_name.to_owned(),
+                                        dep.clone(),
+                                    ),
                                 )),
-                                Err(UOS_IN_PROGRESS) => BlockOnDepFuture { uos: uos_state }
-                                    .await
-                                    .map_err(|f| f(unit_name.to_owned(), dep.clone())),
-                                _ => Ok(()),
+                                Err(UOS_IN_PROGRESS) => {
+                                    recorder.edge(unit_name, dep.inner(), EdgeKind::BlockOn);
+                                    BlockOnDepFuture { uos: uos_state }.await.map_err(|f| {
+                                        (unit_name, f(unit_name.to_owned(), dep.clone()))
+                                    })
+                                }
+                                _ => {
+                                    recorder.cached(unit_name, dep.inner());
+                                    Ok(())
+                                }
                             }
                         })
                     })
                     .collect::<FuturesUnordered<_>>();
 
+                // Grouped by the unit each error happened in
                 let errors = futs
                     .into_stream()
                     .filter_map(|res| ready(res.err()))
-                    .collect::<Vec<_>>()
+                    .fold(HashMap::<&str, Vec<_>>::default(), |mut map, (unit, e)| {
+                        map.entry(unit).or_default().push(e);
+                        ready(map)
+                    })
                     .await;
                 if !errors.is_empty() {
-                    self.notifier.error(&errors);
-                    return Err(RuntimeError::DependencyError(unit_span.clone()));
+                    for (unit, errors) in &errors {
+                        self.events().error(unit, errors);
+                    }
+                    let err = RuntimeError::DependencyError(unit_span.clone());
+                    self.unit_events(scope).unit_skipped(unit_name);
+                    self.flush_log(unit_name).await;
+                    recorder.finish(scope.id, UnitStatus::Skipped, Some(&err));
+                    return Err(err);
                 }
             }
+
+            // Held until the unit's commands have finished,
+            // dependencies are resolved beforehand so they can share the lock
+            let _guard = match unit.lock() {
+                Some(lock) => Some(self.locks[lock].lock().await),
+                None => None,
+            };
+
             for cmd in &unit.commands {
-                cmd.call(self).await?;
+                if let Err(e) = cmd.call(self, scope).await {
+                    self.unit_events(scope).unit_failed(unit_name);
+                    if let Some(logs) = &self.logs {
+                        logs.failure(unit_name, &e);
+                    }
+                    self.flush_log(unit_name).await;
+                    recorder.finish(scope.id, UnitStatus::Failed, Some(&e));
+                    return Err(e);
+                }
             }
 
-            self.notifier.complete(unit_name);
+            self.unit_events(scope).complete(unit_name);
+            self.flush_log(unit_name).await;
+            recorder.finish(scope.id, UnitStatus::Completed, None);
 
             Ok(())
         })
@@ -149,10 +367,45 @@ impl Runtime {
 }
 
 impl Command {
-    pub async fn call(&self, rt: &Runtime) -> Result<(), RuntimeError> {
-        use Command::{Concurrent, DependsOn, Do, Env, Exec, Fs, Io, Meta};
+    pub async fn call(&self, rt: &Runtime, scope: UnitScope<'_>) -> Result<(), RuntimeError> {
+        rt.unit_events(scope).call(scope.name, self);
 
-        rt.notifier.call(self);
+        let start = Instant::now();
+        let mut exit_code = None;
+        let mut output = None;
+        let res = self.dispatch(rt, scope, &mut exit_code, &mut output).await;
+
+        let end = Instant::now();
+        let status = if res.is_ok() {
+            CommandStatus::Success(exit_code)
+        } else {
+            CommandStatus::Failure(exit_code)
+        };
+        rt.unit_events(scope)
+            .command_complete(scope.name, self, end - start, status);
+        rt.flush_log(scope.name).await;
+        scope.recorder.command(
+            scope.id,
+            CommandReport {
+                command: self.clone(),
+                start,
+                end,
+                status,
+                output,
+            },
+        );
+
+        res
+    }
+
+    async fn dispatch(
+        &self,
+        rt: &Runtime,
+        scope: UnitScope<'_>,
+        exit_code: &mut Option<i32>,
+        output: &mut Option<String>,
+    ) -> Result<(), RuntimeError> {
+        use Command::{Concurrent, Custom, DependsOn, Do, Env, Exec, Fs, Io, Meta};
 
         match self {
             // no op, shouldn't be in Vec<Command>
@@ -160,37 +413,39 @@ impl Command {
 
             Do(units) => {
                 for unit in units {
-                    rt.run(unit.inner()).await?;
+                    scope.recorder.edge(scope.name, unit.inner(), EdgeKind::Do);
+                    rt.run_unit(unit.inner(), scope.recorder).await?;
                 }
                 Ok(())
             }
             Exec(cmd) => {
-                let args = cmd[1..].iter().map(Spanned::inner);
-                let handle = duct::cmd(cmd[0].inner(), args)
-                    .full_env(rt.env_vars.load().iter())
-                    .unchecked()
-                    .start()
-                    .map_err(|io| RuntimeError::ExecutionFailure(cmd.to_vec(), io))?;
-                let fut = HandleFuture { handle };
-                let exec = fut
+                let (status, captured) = rt
+                    .exec(cmd, scope)
                     .await
                     .map_err(|io| RuntimeError::ExecutionFailure(cmd.to_vec(), io))?;
-                if exec.status.success() {
+                *exit_code = status.code();
+                output.clone_from(&captured);
+                if status.success() {
                     Ok(())
                 } else {
-                    Err(RuntimeError::ExecutionFailure(
-                        cmd.to_vec(),
-                        io::Error::new(
-                            io::ErrorKind::Other,
-                            format!("Process returned non-successfully with {}.", exec.status),
-                        ),
-                    ))
+                    let io = io::Error::other(format!(
+                        "Process returned non-successfully with {status}."
+                    ));
+                    // Only replayed in the error if it wasn't already shown
+                    Err(match captured {
+                        Some(captured)
+                            if rt.output_mode(scope.name) == OutputMode::QuietSuccess =>
+                        {
+                            RuntimeError::ExecutionFailureWithOutput(cmd.to_vec(), io, captured)
+                        }
+                        _ => RuntimeError::ExecutionFailure(cmd.to_vec(), io),
+                    })
                 }
             }
             Concurrent(cmds) => {
                 let mut errors = cmds
                     .iter()
-                    .map(|cmd| cmd.call(rt))
+                    .map(|cmd| cmd.call(rt, scope))
                     .collect::<FuturesUnordered<_>>()
                     .into_stream()
                     .filter_map(|res| ready(res.err()))
@@ -208,6 +463,60 @@ impl Command {
             Fs(cmd) => cmd.call().await,
             Io(cmd) => cmd.call(),
             Env(cmd) => cmd.call(rt),
+            Custom(module, name, args) => {
+                let handler = rt
+                    .registry
+                    .handler(module.inner(), name.inner())
+                    .ok_or_else(|| RuntimeError::CommandUnsupported(self.clone()))?;
+                let call = Call {
+                    unit: scope.name.to_owned(),
+                    args: args.iter().map(Spanned::inner_owned).collect(),
+                    env: rt.env_vars.load_full(),
+                };
+                handler(call).await.map_err(|e| {
+                    RuntimeError::CustomCommandFailure(module.clone(), name.clone(), e)
+                })
+            }
+        }
+    }
+}
+
+impl Runtime {
+    fn output_mode(&self, unit: &str) -> OutputMode {
+        self.get_unit(unit).1.output().unwrap_or(self.output)
+    }
+
+    /// Writes what's been logged for a unit so far,
+    /// ignoring errors so a log that can't be written doesn't fail the unit.
+    async fn flush_log(&self, unit: &str) {
+        if let Some(logs) = &self.logs {
+            logs.flush(unit).await.ok();
+        }
+    }
+
+    /// Runs a process, returning its output if it was captured.
+    async fn exec(
+        &self,
+        cmd: &[Spanned<String>],
+        scope: UnitScope<'_>,
+    ) -> io::Result<(ExitStatus, Option<String>)> {
+        let mode = self.output_mode(scope.name);
+
+        match (mode, self.logs.as_ref()) {
+            (OutputMode::Inherit, None) if !self.capture && !self.notify_output => {
+                let args = cmd[1..].iter().map(Spanned::inner);
+                let handle = duct::cmd(cmd[0].inner(), args)
+                    .full_env(self.env_vars.load().iter())
+                    .unchecked()
+                    .start()?;
+                Ok((HandleFuture { handle }.await?.status, None))
+            }
+            (mode, logs) => {
+                let envs = self.env_vars.load();
+                let notifier = self.notify_output.then_some(&*self.notifier);
+                output::exec_captured(cmd, &envs, mode, scope.name, logs, self.capture, notifier)
+                    .await
+            }
         }
     }
 }
@@ -348,6 +657,30 @@ pub enum FsError {
     CopyError(Spanned<String>, Spanned<String>, io::Error),
 }
 
+impl FsError {
+    /// Stable code identifying the kind of error, see `august explain`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::CreateFileError(..) => "A0301",
+            Self::CreateDirError(..) => "A0302",
+            Self::RemoveError(..) => "A0303",
+            Self::FileAccessError(..) => "A0304",
+            Self::CopyError(..) => "A0305",
+        }
+    }
+
+    /// Location in the build script of the path that caused the error.
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Self::CreateFileError(p, _)
+            | Self::CreateDirError(p, _)
+            | Self::RemoveError(p, _)
+            | Self::FileAccessError(p, _) => p.span(),
+            Self::CopyError(src, dst, _) => src.span().start..dst.span().end,
+        }
+    }
+}
+
 impl IoCommand {
     pub fn call(&self) -> Result<(), RuntimeError> {
         use IoCommand::{EPrint, EPrintLn, Print, PrintLn};
@@ -453,45 +786,491 @@ impl Future for BlockOnDepFuture<'_> {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
 pub enum NotifierEvent<'a> {
-    Call(&'a Command),
+    Call {
+        unit: &'a str,
+        cmd: &'a Command,
+    },
+    CommandComplete {
+        unit: &'a str,
+        cmd: &'a Command,
+        duration: Duration,
+        status: CommandStatus,
+    },
     Start(&'a str),
+    /// The unit and all of its commands completed successfully.
     Complete(&'a str),
-    Error(&'a [RuntimeError]),
-    Dependency { parent: &'a str, name: &'a str },
-    BlockOn { parent: &'a str, name: &'a str },
+    /// One of the unit's commands failed.
+    UnitFailed(&'a str),
+    /// The unit's commands weren't run as one of its dependencies failed.
+    UnitSkipped(&'a str),
+    /// Errors that stopped the unit, reported once by the unit they happened in.
+    Error {
+        unit: &'a str,
+        errors: &'a [RuntimeError],
+    },
+    Dependency {
+        parent: &'a str,
+        name: &'a str,
+    },
+    BlockOn {
+        parent: &'a str,
+        name: &'a str,
+    },
+    /// Output written by a process spawned by `exec`,
+    /// only sent with [`Runtime::with_output_notifications`].
+    ///
+    /// Contains whole lines, prefixed according to the unit's [`OutputMode`].
+    /// Lines end the same as the process wrote them,
+    /// which could be `\r` for progress bars or nothing at the end of its output.
+    Output {
+        unit: &'a str,
+        text: &'a [u8],
+        stderr: bool,
+    },
 }
 
 /// Trait to hook into runtime events, usually for logging.
 pub trait Notifier {
     fn on_event(&self, event: NotifierEvent<'_>);
+
+    /// Called by the runtime with the monotonic time the event occurred at.
+    ///
+    /// Defaults to [`Notifier::on_event`], override it for notifiers that need the time.
+    fn on_event_at(&self, at: Instant, event: NotifierEvent<'_>) {
+        let _ = at;
+        self.on_event(event);
+    }
+
+    /// Called by the runtime for events about a single invocation of a unit,
+    /// which are [`NotifierEvent::Start`], [`NotifierEvent::Call`], [`NotifierEvent::CommandComplete`],
+    /// [`NotifierEvent::Complete`], [`NotifierEvent::UnitFailed`] and [`NotifierEvent::UnitSkipped`].
+    ///
+    /// `invocation` is unique within a run, telling apart invocations of the same unit
+    /// that run at the same time, such as with `concurrent { do(Test) do(Test) }`.
+    ///
+    /// Defaults to [`Notifier::on_event_at`], override it for notifiers that track running units.
+    fn on_unit_event_at(&self, at: Instant, invocation: usize, event: NotifierEvent<'_>) {
+        let _ = invocation;
+        self.on_event_at(at, event);
+    }
+}
+
+impl<T: Notifier + ?Sized> Notifier for Box<T> {
+    fn on_event(&self, event: NotifierEvent<'_>) {
+        (**self).on_event(event);
+    }
+
+    fn on_event_at(&self, at: Instant, event: NotifierEvent<'_>) {
+        (**self).on_event_at(at, event);
+    }
+
+    fn on_unit_event_at(&self, at: Instant, invocation: usize, event: NotifierEvent<'_>) {
+        (**self).on_unit_event_at(at, invocation, event);
+    }
+}
+
+impl<T: Notifier + ?Sized> Notifier for Arc<T> {
+    fn on_event(&self, event: NotifierEvent<'_>) {
+        (**self).on_event(event);
+    }
+
+    fn on_event_at(&self, at: Instant, event: NotifierEvent<'_>) {
+        (**self).on_event_at(at, event);
+    }
+
+    fn on_unit_event_at(&self, at: Instant, invocation: usize, event: NotifierEvent<'_>) {
+        (**self).on_unit_event_at(at, invocation, event);
+    }
+}
+
+impl<T: Notifier> Notifier for Vec<T> {
+    fn on_event(&self, event: NotifierEvent<'_>) {
+        self.on_event_at(Instant::now(), event);
+    }
+
+    fn on_event_at(&self, at: Instant, event: NotifierEvent<'_>) {
+        for n in self {
+            n.on_event_at(at, event);
+        }
+    }
+
+    fn on_unit_event_at(&self, at: Instant, invocation: usize, event: NotifierEvent<'_>) {
+        for n in self {
+            n.on_unit_event_at(at, invocation, event);
+        }
+    }
+}
+
+macro_rules! tuple_notifier {
+    ($($name:ident)+) => {
+        impl<$($name: Notifier),+> Notifier for ($($name,)+) {
+            fn on_event(&self, event: NotifierEvent<'_>) {
+                self.on_event_at(Instant::now(), event);
+            }
+
+            fn on_event_at(&self, at: Instant, event: NotifierEvent<'_>) {
+                #[allow(non_snake_case)]
+                let ($($name,)+) = self;
+                $($name.on_event_at(at, event);)+
+            }
+
+            fn on_unit_event_at(&self, at: Instant, invocation: usize, event: NotifierEvent<'_>) {
+                #[allow(non_snake_case)]
+                let ($($name,)+) = self;
+                $($name.on_unit_event_at(at, invocation, event);)+
+            }
+        }
+    };
+}
+
+tuple_notifier! { A }
+tuple_notifier! { A B }
+tuple_notifier! { A B C }
+tuple_notifier! { A B C D }
+tuple_notifier! { A B C D E }
+tuple_notifier! { A B C D E F }
+
+/// Sends runtime events to the notifier and unit logs of a [`Runtime`].
+struct Events<'a> {
+    notifier: &'a (dyn Notifier + Sync),
+    logs: Option<&'a UnitLogs>,
+    /// Set for the events of a single invocation of a unit,
+    /// see [`Notifier::on_unit_event_at`]
+    invocation: Option<usize>,
+}
+
+impl Notifier for Events<'_> {
+    fn on_event(&self, event: NotifierEvent<'_>) {
+        self.on_event_at(Instant::now(), event);
+    }
+
+    fn on_event_at(&self, at: Instant, event: NotifierEvent<'_>) {
+        match self.invocation {
+            Some(invocation) => self.notifier.on_unit_event_at(at, invocation, event),
+            None => self.notifier.on_event_at(at, event),
+        }
+        if let Some(logs) = self.logs {
+            logs.on_event_at(at, event);
+        }
+    }
+}
+
+/// Sends every event to each of a set of notifiers, in the order they were added.
+///
+/// An empty [`MultiNotifier`] ignores all events.
+#[derive(Default)]
+pub struct MultiNotifier {
+    notifiers: Vec<Box<dyn Notifier + Sync>>,
+}
+
+impl MultiNotifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn with(mut self, notifier: impl Notifier + Sync + 'static) -> Self {
+        self.push(notifier);
+        self
+    }
+
+    pub fn push(&mut self, notifier: impl Notifier + Sync + 'static) {
+        self.notifiers.push(Box::new(notifier));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notifiers.is_empty()
+    }
+}
+
+impl Notifier for MultiNotifier {
+    fn on_event(&self, event: NotifierEvent<'_>) {
+        self.notifiers.on_event(event);
+    }
+
+    fn on_event_at(&self, at: Instant, event: NotifierEvent<'_>) {
+        self.notifiers.on_event_at(at, event);
+    }
+
+    fn on_unit_event_at(&self, at: Instant, invocation: usize, event: NotifierEvent<'_>) {
+        self.notifiers.on_unit_event_at(at, invocation, event);
+    }
 }
 
 /// Convenience trait for using [`Notifier`]
+///
+/// Events are timestamped with the time each method is called.
 pub trait NotifierExt: Notifier {
-    fn call(&self, command: &Command) {
-        self.on_event(NotifierEvent::Call(command));
+    fn call(&self, unit: &str, cmd: &Command) {
+        self.on_event_at(Instant::now(), NotifierEvent::Call { unit, cmd });
+    }
+
+    fn command_complete(
+        &self,
+        unit: &str,
+        cmd: &Command,
+        duration: Duration,
+        status: CommandStatus,
+    ) {
+        self.on_event_at(
+            Instant::now(),
+            NotifierEvent::CommandComplete {
+                unit,
+                cmd,
+                duration,
+                status,
+            },
+        );
     }
 
     fn start(&self, name: &str) {
-        self.on_event(NotifierEvent::Start(name));
+        self.on_event_at(Instant::now(), NotifierEvent::Start(name));
     }
 
     fn complete(&self, name: &str) {
-        self.on_event(NotifierEvent::Complete(name));
+        self.on_event_at(Instant::now(), NotifierEvent::Complete(name));
+    }
+
+    fn unit_failed(&self, name: &str) {
+        self.on_event_at(Instant::now(), NotifierEvent::UnitFailed(name));
     }
 
-    fn error(&self, errors: &[RuntimeError]) {
-        self.on_event(NotifierEvent::Error(errors));
+    fn unit_skipped(&self, name: &str) {
+        self.on_event_at(Instant::now(), NotifierEvent::UnitSkipped(name));
+    }
+
+    fn error(&self, unit: &str, errors: &[RuntimeError]) {
+        self.on_event_at(Instant::now(), NotifierEvent::Error { unit, errors });
     }
 
     fn dependency(&self, parent: &str, name: &str) {
-        self.on_event(NotifierEvent::Dependency { parent, name });
+        self.on_event_at(Instant::now(), NotifierEvent::Dependency { parent, name });
     }
 
     fn block_on(&self, parent: &str, name: &str) {
-        self.on_event(NotifierEvent::BlockOn { parent, name });
+        self.on_event_at(Instant::now(), NotifierEvent::BlockOn { parent, name });
+    }
+
+    fn output(&self, unit: &str, text: &[u8], stderr: bool) {
+        self.on_event_at(Instant::now(), NotifierEvent::Output { unit, text, stderr });
     }
 }
 
 impl<T: Notifier + ?Sized> NotifierExt for T {}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread::sleep,
+        time::Duration,
+    };
+
+    use tokio::task::{spawn_blocking, yield_now};
+
+    use super::{log_path, MultiNotifier, OutputMode, RunReport, Runtime, UnitStatus};
+    use crate::{parse_str_with, registry::CommandRegistry};
+
+    /// A runtime with `test::sleep(<millis>)` and `test::fail()` available,
+    /// along with any other commands added to `registry`.
+    fn runtime(code: &str, mut registry: CommandRegistry) -> Runtime {
+        registry
+            .module("test")
+            .unwrap()
+            .command("sleep", |call| async move {
+                let millis = call.args()[0].parse()?;
+                spawn_blocking(move || sleep(Duration::from_millis(millis))).await?;
+                Ok(())
+            })
+            .command("fail", |_| async { Err("failed on purpose".into()) });
+
+        let module = parse_str_with("main.august", code, &registry).unwrap();
+        Runtime::new(module, MultiNotifier::default()).with_registry(registry)
+    }
+
+    /// Runs `Root` on a current thread Tokio runtime, like `#[tokio::test]` uses.
+    fn run(rt: &Runtime) -> RunReport {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(rt.run("Root"))
+    }
+
+    /// Most `test::work` commands seen running at the same time.
+    fn max_overlap(code: &str) -> usize {
+        let active = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+
+        let mut registry = CommandRegistry::new();
+        registry.module("test").unwrap().command("work", {
+            let (active, max) = (active.clone(), max.clone());
+            move |_| {
+                let (active, max) = (active.clone(), max.clone());
+                async move {
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max.fetch_max(now, Ordering::SeqCst);
+                    // Gives other units the chance to run in the meantime
+                    for _ in 0..10 {
+                        yield_now().await;
+                    }
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    Ok(())
+                }
+            }
+        });
+
+        let report = run(&runtime(code, registry));
+        assert!(report.is_success(), "{:?}", report.error);
+        max.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn units_without_locks_overlap() {
+        let code = r#"
+            unit Root {
+                depends_on(A, B)
+            }
+
+            unit A {
+                test::work()
+            }
+
+            unit B {
+                test::work()
+            }
+        "#;
+
+        assert_eq!(max_overlap(code), 2);
+    }
+
+    #[test]
+    fn units_sharing_a_lock_never_overlap() {
+        let code = r#"
+            unit Root {
+                depends_on(A, B)
+            }
+
+            unit A {
+                meta(@lock "shared")
+                test::work()
+            }
+
+            unit B {
+                meta(@lock "shared")
+                test::work()
+            }
+        "#;
+
+        assert_eq!(max_overlap(code), 1);
+    }
+
+    #[test]
+    fn reports_the_critical_path() {
+        let code = r#"
+            unit Root {
+                depends_on(Slow, Fast)
+            }
+
+            unit Slow {
+                depends_on(Shared)
+                test::sleep("100")
+            }
+
+            unit Fast {
+                depends_on(Shared)
+                test::sleep("10")
+            }
+
+            unit Shared {
+                test::sleep("10")
+            }
+        "#;
+        let report = run(&runtime(code, CommandRegistry::new()));
+
+        assert!(report.is_success(), "{:?}", report.error);
+        for name in ["Root", "Slow", "Fast", "Shared"] {
+            assert_eq!(report.unit(name).unwrap().status, UnitStatus::Completed);
+        }
+        let path = report
+            .critical_path()
+            .into_iter()
+            .map(|u| u.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(path, ["Root", "Slow", "Shared"]);
+    }
+
+    #[test]
+    fn reports_failed_and_skipped_units() {
+        let code = r#"
+            unit Root {
+                depends_on(Broken, Fine)
+            }
+
+            unit Broken {
+                test::fail()
+            }
+
+            unit Fine {
+                test::sleep("1")
+            }
+        "#;
+        let report = run(&runtime(code, CommandRegistry::new()));
+
+        assert!(!report.is_success());
+        let status = |name| report.unit(name).unwrap().status;
+        assert_eq!(status("Root"), UnitStatus::Skipped);
+        assert_eq!(status("Broken"), UnitStatus::Failed);
+        assert_eq!(status("Fine"), UnitStatus::Completed);
+        assert_eq!(
+            report.unit("Broken").unwrap().error.as_deref(),
+            Some("test::fail failed: failed on purpose")
+        );
+    }
+
+    #[test]
+    fn keeps_line_endings_of_captured_output() {
+        let code = r#"
+            unit Root {
+                exec(printf "first\rsecond\r\nthird")
+            }
+        "#;
+        let rt = runtime(code, CommandRegistry::new())
+            .with_output(OutputMode::QuietSuccess)
+            .with_captured_output();
+        let report = run(&rt);
+
+        assert!(report.is_success(), "{:?}", report.error);
+        let output = report.unit("Root").unwrap().commands[0].output.as_deref();
+        assert_eq!(output, Some("first\rsecond\r\nthird"));
+    }
+
+    #[test]
+    fn writes_unit_logs() {
+        let dir = env::temp_dir().join(format!("august-logs-{}", std::process::id()));
+        let code = r#"
+            unit Root {
+                exec(printf "out")
+            }
+        "#;
+        let rt = runtime(code, CommandRegistry::new())
+            .with_output(OutputMode::QuietSuccess)
+            .with_log_dir(&dir);
+        let report = run(&rt);
+
+        assert!(report.is_success(), "{:?}", report.error);
+        let log = fs::read_to_string(log_path(&dir, "Root")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        // Output without a trailing newline is followed directly by the next event
+        assert!(log.contains("] Begining work\n"), "{log}");
+        assert!(log.contains("] Calling exec(printf out)\nout["), "{log}");
+        assert!(log.ends_with("] Completed\n"), "{log}");
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    mem,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use tokio::{
+    fs::{create_dir_all, File},
+    io::{self, AsyncWriteExt},
+};
+
+use crate::HashMap;
+
+use super::{Notifier, NotifierEvent, RuntimeError};
+
+/// Path of the log file for a unit, within a log directory
+/// given to [`Runtime::with_log_dir`](super::Runtime::with_log_dir).
+pub fn log_path(dir: &Path, unit: &str) -> PathBuf {
+    dir.join(format!("{unit}.log"))
+}
+
+/// Writes the events and `exec` output of each unit to `<dir>/<unit>.log`.
+///
+/// Events are sent to notifiers outside of async code,
+/// so they're kept until the runtime flushes the unit's log.
+#[derive(Debug)]
+pub(crate) struct UnitLogs {
+    dir: PathBuf,
+    started: Instant,
+    logs: Mutex<HashMap<String, Arc<UnitLog>>>,
+}
+
+#[derive(Debug, Default)]
+struct UnitLog {
+    /// Written to the file on the next flush
+    pending: Mutex<Vec<u8>>,
+    /// Created on the first flush
+    file: tokio::sync::Mutex<Option<File>>,
+}
+
+impl UnitLogs {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            started: Instant::now(),
+            logs: Mutex::default(),
+        }
+    }
+
+    fn log(&self, unit: &str) -> Arc<UnitLog> {
+        let mut logs = self.logs.lock().unwrap();
+        Arc::clone(logs.entry(unit.to_owned()).or_default())
+    }
+
+    /// Adds output to a unit's log, written on the next flush.
+    pub fn write(&self, unit: &str, text: &[u8]) {
+        self.log(unit)
+            .pending
+            .lock()
+            .unwrap()
+            .extend_from_slice(text);
+    }
+
+    /// Writes everything logged for a unit so far to its file, creating it on first use.
+    pub async fn flush(&self, unit: &str) -> io::Result<()> {
+        let log = self.log(unit);
+        // Held while taking what's pending, so concurrent flushes keep the order it was written in
+        let mut file = log.file.lock().await;
+        let pending = mem::take(&mut *log.pending.lock().unwrap());
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let file = match &mut *file {
+            Some(file) => file,
+            None => {
+                create_dir_all(&self.dir).await?;
+                file.insert(File::create(log_path(&self.dir, unit)).await?)
+            }
+        };
+        file.write_all(&pending).await?;
+        file.flush().await
+    }
+
+    /// Writes the error that made a unit fail.
+    pub fn failure(&self, unit: &str, error: &RuntimeError) {
+        self.line(Instant::now(), unit, format_args!("Error: {error}"));
+    }
+
+    fn line(&self, at: Instant, unit: &str, text: std::fmt::Arguments<'_>) {
+        let elapsed = at.saturating_duration_since(self.started);
+        self.write(unit, format!("[{elapsed:.3?}] {text}\n").as_bytes());
+    }
+}
+
+impl Notifier for UnitLogs {
+    fn on_event(&self, event: NotifierEvent<'_>) {
+        self.on_event_at(Instant::now(), event);
+    }
+
+    fn on_event_at(&self, at: Instant, event: NotifierEvent<'_>) {
+        match event {
+            NotifierEvent::Call { unit, cmd } => self.line(at, unit, format_args!("Calling {cmd}")),
+            NotifierEvent::CommandComplete {
+                unit,
+                cmd,
+                duration,
+                status,
+            } => self.line(
+                at,
+                unit,
+                format_args!(
+                    "Finished {cmd} in {duration:.2?} ({})",
+                    match (status.is_success(), status.exit_code()) {
+                        (true, _) => "success".to_owned(),
+                        (false, Some(c)) => format!("failed with exit code {c}"),
+                        (false, None) => "failed".to_owned(),
+                    }
+                ),
+            ),
+            NotifierEvent::Start(name) => self.line(at, name, format_args!("Begining work")),
+            NotifierEvent::Complete(name) => self.line(at, name, format_args!("Completed")),
+            NotifierEvent::UnitFailed(name) => self.line(at, name, format_args!("Failed")),
+            NotifierEvent::UnitSkipped(name) => {
+                self.line(at, name, format_args!("Skipped as a dependency failed"));
+            }
+            NotifierEvent::Dependency { parent, name } => {
+                self.line(at, parent, format_args!("Spawning dependency {name}"));
+            }
+            NotifierEvent::BlockOn { parent, name } => {
+                self.line(at, parent, format_args!("Blocking until {name} completes"));
+            }
+            // Written by the runtime with the unit that failed, see `UnitLogs::failure`
+            NotifierEvent::Error { .. } => {}
+            // Copied to the log as the process writes it
+            NotifierEvent::Output { .. } => {}
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    ffi::OsString,
+    io::{self, Write},
+    process::{ExitStatus, Stdio},
+    str::FromStr,
+    sync::Mutex,
+};
+
+use futures::join;
+use thiserror::Error;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader};
+
+use crate::{parser::Spanned, HashMap};
+
+use super::{logs::UnitLogs, Notifier, NotifierExt};
+
+/// How output from processes spawned by `exec` is written.
+///
+/// Can be set for the whole runtime with [`Runtime::with_output`](super::Runtime::with_output)
+/// or for a single unit with `@output` in its `meta`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Processes write directly to the terminal.
+    #[default]
+    Inherit,
+    /// Each line is tagged with the name of the unit that wrote it.
+    Prefixed,
+    /// Output is buffered and written all at once when the process exits.
+    ///
+    /// All of stdout is written before stderr,
+    /// so the order lines were written in across the two is lost.
+    Grouped,
+    /// Output is discarded if the process succeeds,
+    /// otherwise it is kept in the [`RuntimeError`](super::RuntimeError) for reporting.
+    QuietSuccess,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Unknown output mode {0}, expected one of inherit, prefixed, grouped or quiet-success")]
+pub struct UnknownOutputMode(pub String);
+
+impl FromStr for OutputMode {
+    type Err = UnknownOutputMode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inherit" => Ok(Self::Inherit),
+            "prefixed" => Ok(Self::Prefixed),
+            "grouped" => Ok(Self::Grouped),
+            "quiet-success" => Ok(Self::QuietSuccess),
+            _ => Err(UnknownOutputMode(s.to_owned())),
+        }
+    }
+}
+
+/// Runs a process with its output piped back through August
+/// so it can be written according to `mode` and copied to a log file.
+///
+/// Returns the combined output of the process if `capture` is set
+/// or it was run with [`OutputMode::QuietSuccess`].
+///
+/// Output is sent to `notifier` instead of stdout and stderr if there is one.
+pub(super) async fn exec_captured(
+    args: &[Spanned<String>],
+    envs: &HashMap<OsString, OsString>,
+    mode: OutputMode,
+    unit: &str,
+    logs: Option<&UnitLogs>,
+    capture: bool,
+    notifier: Option<&(dyn Notifier + Sync)>,
+) -> io::Result<(ExitStatus, Option<String>)> {
+    let out = Output { unit, notifier };
+
+    let mut child = tokio::process::Command::new(args[0].inner())
+        .args(args[1..].iter().map(Spanned::inner))
+        .env_clear()
+        .envs(envs.iter())
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let capture = capture || mode == OutputMode::QuietSuccess;
+    let captured = capture.then(|| Mutex::new(Vec::new()));
+
+    if mode == OutputMode::Grouped {
+        let output = child.wait_with_output().await?;
+        out.write(&output.stdout, false)?;
+        out.write(&output.stderr, true)?;
+        if let Some(logs) = logs {
+            logs.write(unit, &output.stdout);
+            logs.write(unit, &output.stderr);
+            logs.flush(unit).await?;
+        }
+        let captured = capture.then(|| {
+            String::from_utf8_lossy(&[output.stdout, output.stderr].concat()).into_owned()
+        });
+        return Ok((output.status, captured));
+    }
+
+    let (out_sink, err_sink) = match mode {
+        OutputMode::QuietSuccess => (Sink::Discard, Sink::Discard),
+        OutputMode::Prefixed => {
+            let prefix = format!("[{unit}] ");
+            (
+                Sink::Stdout(prefix.clone().into_bytes()),
+                Sink::Stderr(prefix.into_bytes()),
+            )
+        }
+        _ => (Sink::Stdout(Vec::new()), Sink::Stderr(Vec::new())),
+    };
+
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let (out, err) = join!(
+        write_lines(stdout, out_sink, out, logs, captured.as_ref()),
+        write_lines(stderr, err_sink, out, logs, captured.as_ref())
+    );
+    out?;
+    err?;
+
+    let status = child.wait().await?;
+    let captured = captured.map(|c| String::from_utf8_lossy(&c.into_inner().unwrap()).into_owned());
+    Ok((status, captured))
+}
+
+/// Where lines read from a process are written.
+enum Sink {
+    /// Writes to stdout with a prefix on each line
+    Stdout(Vec<u8>),
+    /// Writes to stderr with a prefix on each line
+    Stderr(Vec<u8>),
+    Discard,
+}
+
+/// Where output is written, either directly to stdout and stderr or through a notifier.
+#[derive(Clone, Copy)]
+struct Output<'a> {
+    unit: &'a str,
+    notifier: Option<&'a (dyn Notifier + Sync)>,
+}
+
+impl Output<'_> {
+    fn write(&self, text: &[u8], stderr: bool) -> io::Result<()> {
+        match self.notifier {
+            Some(notifier) => {
+                if !text.is_empty() {
+                    notifier.output(self.unit, text, stderr);
+                }
+                Ok(())
+            }
+            None if stderr => io::stderr().lock().write_all(text),
+            None => io::stdout().lock().write_all(text),
+        }
+    }
+}
+
+/// Copies lines from a process to a [`Sink`],
+/// as well as the log file and capture buffer if there are any.
+async fn write_lines(
+    r: impl AsyncRead + Unpin,
+    sink: Sink,
+    out: Output<'_>,
+    logs: Option<&UnitLogs>,
+    captured: Option<&Mutex<Vec<u8>>>,
+) -> io::Result<()> {
+    let mut r = BufReader::new(r);
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        if read_line(&mut r, &mut line).await? == 0 {
+            break;
+        }
+        if let Some(logs) = logs {
+            logs.write(out.unit, &line);
+            logs.flush(out.unit).await?;
+        }
+        if let Some(captured) = captured {
+            captured.lock().unwrap().extend_from_slice(&line);
+        }
+
+        // Whole lines are written at once so they don't interleave
+        match &sink {
+            Sink::Stdout(prefix) => out.write(&[prefix, &line[..]].concat(), false)?,
+            Sink::Stderr(prefix) => out.write(&[prefix, &line[..]].concat(), true)?,
+            Sink::Discard => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads up to and including the next `\n` or `\r`, or the rest of the output at the end,
+/// so progress lines ending in `\r` are written as soon as they're complete.
+///
+/// Returns the number of bytes read, which is zero at the end.
+async fn read_line(r: &mut (impl AsyncBufRead + Unpin), line: &mut Vec<u8>) -> io::Result<usize> {
+    loop {
+        let buf = r.fill_buf().await?;
+        if buf.is_empty() {
+            return Ok(line.len());
+        }
+
+        match buf.iter().position(|b| matches!(b, b'\n' | b'\r')) {
+            Some(i) => {
+                // Keeps `\r\n` together if it's already been read
+                let end = if buf[i] == b'\r' && buf.get(i + 1) == Some(&b'\n') {
+                    i + 2
+                } else {
+                    i + 1
+                };
+                line.extend_from_slice(&buf[..end]);
+                r.consume(end);
+                return Ok(line.len());
+            }
+            None => {
+                let len = buf.len();
+                line.extend_from_slice(buf);
+                r.consume(len);
+            }
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{Command, HashMap};
+
+use super::RuntimeError;
+
+/// Summary of a single call to [`Runtime::run`](super::Runtime::run).
+#[derive(Debug)]
+pub struct RunReport {
+    pub started: Instant,
+    pub finished: Instant,
+    /// Every unit invocation in the order they began.
+    pub units: Vec<UnitReport>,
+    /// Dependency and `do` edges taken while running.
+    pub edges: Vec<Edge>,
+    /// The error the root unit failed with, if any.
+    pub error: Option<RuntimeError>,
+}
+
+impl RunReport {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.finished - self.started
+    }
+
+    /// Finds the latest invocation of a unit by name.
+    pub fn unit(&self, name: &str) -> Option<&UnitReport> {
+        self.units.iter().rev().find(|u| u.name == name)
+    }
+
+    /// Chain of dependencies that determined when the run finished.
+    ///
+    /// Starts at the root unit and repeatedly follows the `depends_on` edge
+    /// to the dependency with the longest path of its own,
+    /// where each unit adds the time it took once its dependencies were done.
+    pub fn critical_path(&self) -> Vec<&UnitReport> {
+        let mut lengths = HashMap::default();
+        let mut path: Vec<&UnitReport> = Vec::new();
+        let mut next = self.units.first();
+
+        while let Some(unit) = next {
+            path.push(unit);
+            next = self
+                .dependencies(unit)
+                .filter(|d| !path.iter().any(|p| p.name == d.name))
+                .max_by_key(|d| self.path_length(d, &mut lengths));
+        }
+
+        path
+    }
+
+    /// Latest invocations of the units that `unit` depends on.
+    fn dependencies<'a>(&'a self, unit: &'a UnitReport) -> impl Iterator<Item = &'a UnitReport> {
+        self.edges
+            .iter()
+            .filter(move |e| e.parent == unit.name && e.kind != EdgeKind::Do)
+            .filter_map(|e| self.unit(&e.child))
+    }
+
+    fn path_length<'a>(
+        &'a self,
+        unit: &'a UnitReport,
+        lengths: &mut HashMap<&'a str, Duration>,
+    ) -> Duration {
+        if let Some(length) = lengths.get(unit.name.as_str()) {
+            return *length;
+        }
+        // Guards against cycles in the recorded edges
+        lengths.insert(&unit.name, Duration::ZERO);
+
+        let mut deps_done = unit.start;
+        let mut longest = Duration::ZERO;
+        for dep in self.dependencies(unit) {
+            deps_done = deps_done.max(dep.end);
+            longest = longest.max(self.path_length(dep, lengths));
+        }
+
+        let length = longest + unit.end.saturating_duration_since(deps_done);
+        lengths.insert(&unit.name, length);
+        length
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnitReport {
+    pub name: String,
+    pub status: UnitStatus,
+    pub start: Instant,
+    pub end: Instant,
+    /// Commands in the order they finished, including those nested in
+    /// `concurrent` blocks.
+    pub commands: Vec<CommandReport>,
+    /// Text of the error that stopped the unit.
+    pub error: Option<String>,
+}
+
+impl UnitReport {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitStatus {
+    Completed,
+    /// One of the unit's commands failed.
+    Failed,
+    /// The unit's commands never ran because a dependency failed.
+    Skipped,
+    /// The unit had already completed before this run started.
+    Cached,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandReport {
+    pub command: Command,
+    pub start: Instant,
+    pub end: Instant,
+    pub status: CommandStatus,
+    /// Combined stdout and stderr of `exec` calls, if it was captured,
+    /// see [`Runtime::with_captured_output`](super::Runtime::with_captured_output).
+    pub output: Option<String>,
+}
+
+impl CommandReport {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+/// Outcome of a command, with the exit code for processes spawned by `exec`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandStatus {
+    Success(Option<i32>),
+    Failure(Option<i32>),
+}
+
+impl CommandStatus {
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Success(_))
+    }
+
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            Self::Success(c) | Self::Failure(c) => *c,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub parent: String,
+    pub child: String,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// The child was spawned to resolve a dependency of the parent.
+    Dependency,
+    /// The parent waited on a dependency another unit had already spawned.
+    BlockOn,
+    /// The dependency had already completed.
+    Complete,
+    /// The parent ran the child with `do`.
+    Do,
+}
+
+/// Collects the pieces of a [`RunReport`] while the runtime is working.
+#[derive(Debug, Default)]
+pub(crate) struct Recorder {
+    inner: Mutex<Recording>,
+}
+
+#[derive(Debug, Default)]
+struct Recording {
+    units: Vec<UnitReport>,
+    edges: Vec<Edge>,
+}
+
+impl Recorder {
+    pub fn start(&self, name: &str) -> usize {
+        let now = Instant::now();
+        let mut rec = self.inner.lock().unwrap();
+        rec.units.push(UnitReport {
+            name: name.to_owned(),
+            // Every unit that starts is finished before the report is taken
+            status: UnitStatus::Completed,
+            start: now,
+            end: now,
+            commands: Vec::new(),
+            error: None,
+        });
+        rec.units.len() - 1
+    }
+
+    pub fn finish(&self, id: usize, status: UnitStatus, error: Option<&RuntimeError>) {
+        let mut rec = self.inner.lock().unwrap();
+        let unit = &mut rec.units[id];
+        unit.status = status;
+        unit.end = Instant::now();
+        unit.error = error.map(ToString::to_string);
+    }
+
+    /// Records a dependency that was already complete,
+    /// adding a cached entry if it hasn't run during this recording.
+    pub fn cached(&self, parent: &str, name: &str) {
+        let now = Instant::now();
+        let mut rec = self.inner.lock().unwrap();
+        if !rec.units.iter().any(|u| u.name == name) {
+            rec.units.push(UnitReport {
+                name: name.to_owned(),
+                status: UnitStatus::Cached,
+                start: now,
+                end: now,
+                commands: Vec::new(),
+                error: None,
+            });
+        }
+        rec.edges.push(Edge {
+            parent: parent.to_owned(),
+            child: name.to_owned(),
+            kind: EdgeKind::Complete,
+        });
+    }
+
+    pub fn edge(&self, parent: &str, child: &str, kind: EdgeKind) {
+        self.inner.lock().unwrap().edges.push(Edge {
+            parent: parent.to_owned(),
+            child: child.to_owned(),
+            kind,
+        });
+    }
+
+    pub fn command(&self, id: usize, report: CommandReport) {
+        self.inner.lock().unwrap().units[id].commands.push(report);
+    }
+
+    pub fn into_report(self, started: Instant, error: Option<RuntimeError>) -> RunReport {
+        let rec = self.inner.into_inner().unwrap();
+        RunReport {
+            started,
+            finished: Instant::now(),
+            units: rec.units,
+            edges: rec.edges,
+            error,
+        }
+    }
+}
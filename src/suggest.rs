@@ -0,0 +1,84 @@
+//! Finding the closest match to a misspelt name, for "did you mean" suggestions.
+
+/// Finds the candidate closest to `name`,
+/// as long as it's close enough to likely be what was meant.
+///
+/// Letter case is ignored, so `biuld` will suggest `Build`.
+/// Candidates that are the same as `name` apart from case aren't suggested,
+/// as `name` is what was already found.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let max = (name.chars().count() / 3).max(1);
+
+    candidates
+        .into_iter()
+        .map(|c| (distance(&name, &c.to_lowercase()), c))
+        .filter(|(d, _)| (1..=max).contains(d))
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+/// Edit distance between two strings,
+/// counting insertions, deletions, substitutions and swaps of adjacent characters.
+fn distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j - 1] + cost)
+                .min(d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::closest;
+
+    #[test]
+    fn skips_the_name_itself() {
+        assert_eq!(closest("as", ["as", "is"]), Some("is"));
+        assert_eq!(closest("Build", ["build"]), None);
+    }
+
+    #[test]
+    fn allows_a_third_of_the_name_to_differ() {
+        // One edit is always allowed, even for short names
+        assert_eq!(closest("fs", ["io"]), None);
+        assert_eq!(closest("fss", ["fs"]), Some("fs"));
+        assert_eq!(closest("depens_n", ["depends_on"]), Some("depends_on"));
+        assert_eq!(closest("depns_n", ["depends_on"]), None);
+    }
+
+    #[test]
+    fn counts_swapped_letters_as_one_edit() {
+        assert_eq!(closest("biuld", ["build"]), Some("build"));
+        assert_eq!(closest("bilud", ["build"]), None);
+    }
+
+    #[test]
+    fn ignores_case() {
+        assert_eq!(closest("BIULD", ["Test", "Build"]), Some("Build"));
+    }
+
+    #[test]
+    fn picks_the_closest() {
+        assert_eq!(closest("copi", ["copy", "copy_to"]), Some("copy"));
+    }
+}
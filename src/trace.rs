@@ -0,0 +1,119 @@
+use std::{io::Write, time::Instant};
+
+use august_build::{
+    runtime::{RunReport, UnitStatus},
+    Command,
+};
+use serde_json::{json, Value};
+
+/// A slice of time on one of the trace's lanes.
+struct Slice {
+    name: String,
+    cat: &'static str,
+    start: Instant,
+    end: Instant,
+    /// Index into [`RunReport::units`] of the unit this slice belongs to
+    unit: usize,
+    args: Value,
+}
+
+/// Writes a run as a Chrome Trace Event Format file,
+/// viewable with `chrome://tracing` or Perfetto.
+///
+/// Units and `exec` calls become duration slices.
+/// Slices that overlap without nesting are placed on separate lanes,
+/// so the number of lanes shows how much of the run happened concurrently.
+pub fn write_trace(report: &RunReport, w: &mut dyn Write) -> std::io::Result<()> {
+    let mut slices = Vec::new();
+
+    for (i, unit) in report.units.iter().enumerate() {
+        if unit.status == UnitStatus::Cached {
+            continue;
+        }
+
+        slices.push(Slice {
+            name: unit.name.clone(),
+            cat: "unit",
+            start: unit.start,
+            end: unit.end,
+            unit: i,
+            args: json!({ "status": format!("{:?}", unit.status) }),
+        });
+
+        for cmd in &unit.commands {
+            if let Command::Exec(_) = cmd.command {
+                slices.push(Slice {
+                    name: cmd.command.to_string(),
+                    cat: "exec",
+                    start: cmd.start,
+                    end: cmd.end,
+                    unit: i,
+                    args: json!({
+                        "unit": unit.name,
+                        "success": cmd.status.is_success(),
+                        "exit_code": cmd.status.exit_code(),
+                    }),
+                });
+            }
+        }
+    }
+
+    // Outer slices first so nested slices can be placed inside them
+    slices.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+
+    let mut lanes: Vec<Vec<Instant>> = Vec::new();
+    let mut unit_lanes = vec![0; report.units.len()];
+    let mut events = Vec::with_capacity(slices.len());
+
+    for slice in slices {
+        let fits = |lane: &mut Vec<Instant>| {
+            while lane.last().is_some_and(|end| *end <= slice.start) {
+                lane.pop();
+            }
+            lane.last().is_none_or(|end| *end >= slice.end)
+        };
+
+        let preferred = (slice.cat != "unit").then_some(unit_lanes[slice.unit]);
+        let lane = match preferred.filter(|l| fits(&mut lanes[*l])) {
+            Some(l) => l,
+            None => match lanes.iter_mut().position(fits) {
+                Some(l) => l,
+                None => {
+                    lanes.push(Vec::new());
+                    lanes.len() - 1
+                }
+            },
+        };
+        lanes[lane].push(slice.end);
+        if slice.cat == "unit" {
+            unit_lanes[slice.unit] = lane;
+        }
+
+        events.push(json!({
+            "name": slice.name,
+            "cat": slice.cat,
+            "ph": "X",
+            "ts": (slice.start - report.started).as_micros() as u64,
+            "dur": (slice.end - slice.start).as_micros() as u64,
+            "pid": 1,
+            "tid": lane,
+            "args": slice.args,
+        }));
+    }
+
+    events.extend((0..lanes.len()).map(|lane| {
+        json!({
+            "name": "thread_name",
+            "ph": "M",
+            "pid": 1,
+            "tid": lane,
+            "args": { "name": format!("Lane {lane}") },
+        })
+    }));
+
+    serde_json::to_writer(
+        &mut *w,
+        &json!({ "traceEvents": events, "displayTimeUnit": "ms" }),
+    )?;
+    writeln!(w)
+}